@@ -0,0 +1,79 @@
+## Captcha
+
+captcha-question = { $name }: { $question }
+captcha-reply-hint = Reply to this message with the answer.
+captcha-puzzle-add = What is { $a } + { $b }?
+captcha-puzzle-subtract = What is { $a } - { $b }?
+captcha-puzzle-multiply = What is { $a } × { $b }?
+captcha-puzzle-reverse = Reply with the word "{ $word }" spelled backwards.
+captcha-puzzle-count = How many letters are in the word "{ $word }"?
+captcha-puzzle-image = Type the characters shown in the image.
+
+## Welcome
+
+welcome-rules-hint = React with ✅ to accept the rules.
+
+## Commands
+
+command-usage =
+    Commands:
+    !t1 vouch <user ID>
+    !t1 acl add <server>
+    !t1 acl remove <server>
+    !t1 pardon <user ID>
+    !t1 history [user ID] [room ID] [since, e.g. 7d]
+command-vouched = Vouched for { $user }.
+command-invalid-user = { $user } is not a valid user ID.
+command-invalid-server = { $server } is not a valid server name.
+command-acl-added = Denied { $server } in the protected rooms.
+command-acl-allowlisted = { $server } is allowlisted and cannot be denied.
+command-acl-removed = Removed { $server } from the deny list of the protected rooms.
+command-pardoned = Pardoned { $user } and unbanned them from { $count ->
+    [one] { $count } room
+   *[other] { $count } rooms
+}.
+command-admin-only = Only the bot admins can run this command.
+command-history-empty = No violations recorded.
+
+## Appeals
+
+appeal-forwarded = Your appeal was forwarded to the moderators.
+appeal-pending = Your appeal is waiting for a moderator.
+appeal-not-banned = You are not banned from any room protected by this bot.
+appeal-accepted = Your appeal was accepted, you can join the rooms again.
+
+## Log room
+
+log-missing-power = Missing permissions in { $room }: { $permissions }. These actions are skipped until the permissions are granted.
+log-power-restored = All permissions are granted in { $room } again.
+log-invited-join = { $user } joined { $room }, invited by { $inviter }
+log-report-kick = Would have kicked { $user } from { $room }: { $reason }
+log-report-ban = Would have banned { $user } from { $room }: { $reason }
+log-report-revoke-invite = Would have revoked the invite of { $user } to { $room }
+log-report-redact = Would have redacted a message of { $user } in { $room }: { $reason }
+log-report-quarantine = Would have restricted { $user } in { $room }: { $reason }
+log-report-server-acl = Would have denied { $server } in { $room }
+log-appeal = { $user } appeals their ban from { $rooms }: { $message }
+log-appeal-history = Recorded violations:
+log-appeal-hint = React with ✅ to unban them.
+log-pardoned = { $moderator } pardoned { $user }, who was unbanned from { $count ->
+    [one] { $count } room
+   *[other] { $count } rooms
+}.
+permission-kick = kick
+permission-ban = ban
+permission-redact = redact
+permission-power-levels = change power levels
+permission-server-acl = change the server ACL
+
+## Moderation reasons
+
+reason-invite-revoked = Invite revoked
+reason-captcha-answer = Captcha answer
+reason-quarantine = Captcha not answered yet
+violation-spam = Spam
+violation-likely-bot = Failed the captcha
+violation-impersonation = Impersonating a room member
+violation-offensive-display-name = Offensive display name
+violation-invite-spam = Too many invites
+violation-policy-list = Banned by a policy list
@@ -0,0 +1,73 @@
+## Captcha
+
+captcha-question = { $name }さん：{ $question }
+captcha-reply-hint = このメッセージに返信して答えてください。
+captcha-puzzle-add = { $a } + { $b } はいくつですか？
+captcha-puzzle-subtract = { $a } - { $b } はいくつですか？
+captcha-puzzle-multiply = { $a } × { $b } はいくつですか？
+captcha-puzzle-reverse = 「{ $word }」を逆から綴って返信してください。
+captcha-puzzle-count = 「{ $word }」は何文字ですか？
+captcha-puzzle-image = 画像に表示されている文字を入力してください。
+
+## Welcome
+
+welcome-rules-hint = ルールに同意する場合は ✅ でリアクションしてください。
+
+## Commands
+
+command-usage =
+    コマンド：
+    !t1 vouch <ユーザー ID>
+    !t1 acl add <サーバー>
+    !t1 acl remove <サーバー>
+    !t1 pardon <ユーザー ID>
+    !t1 history [ユーザー ID] [ルーム ID] [期間 (例: 7d)]
+command-vouched = { $user } さんを保証しました。
+command-invalid-user = { $user } は有効なユーザー ID ではありません。
+command-invalid-server = { $server } は有効なサーバー名ではありません。
+command-acl-added = 保護されたルームで { $server } を拒否しました。
+command-acl-allowlisted = { $server } は許可リストにあるため拒否できません。
+command-acl-removed = 保護されたルームの拒否リストから { $server } を削除しました。
+command-pardoned = { $user } を赦免し、{ $count } 個のルームで BAN を解除しました。
+command-admin-only = このコマンドはボットの管理者のみ実行できます。
+command-history-empty = 記録された違反はありません。
+
+## Appeals
+
+appeal-forwarded = 異議申し立てをモデレーターに転送しました。
+appeal-pending = 異議申し立てはモデレーターの確認待ちです。
+appeal-not-banned = このボットが保護しているルームで BAN されていません。
+appeal-accepted = 異議申し立てが認められました。再びルームに参加できます。
+
+## Log room
+
+log-missing-power = { $room } で権限が不足しています: { $permissions }。権限が付与されるまで、これらの操作は行いません。
+log-power-restored = { $room } ですべての権限が再び付与されました。
+log-invited-join = { $user } が { $inviter } の招待で { $room } に参加しました
+log-report-kick = { $room } から { $user } をキックするところでした: { $reason }
+log-report-ban = { $room } から { $user } を BAN するところでした: { $reason }
+log-report-revoke-invite = { $room } への { $user } の招待を取り消すところでした
+log-report-redact = { $room } で { $user } のメッセージを削除するところでした: { $reason }
+log-report-quarantine = { $room } で { $user } を制限するところでした: { $reason }
+log-report-server-acl = { $room } で { $server } を拒否するところでした
+log-appeal = { $user } が { $rooms } での BAN に異議を申し立てています: { $message }
+log-appeal-history = 記録された違反:
+log-appeal-hint = ✅ でリアクションすると BAN を解除します。
+log-pardoned = { $moderator } が { $user } を赦免し、{ $count } 個のルームで BAN を解除しました。
+permission-kick = キック
+permission-ban = BAN
+permission-redact = メッセージの削除
+permission-power-levels = 権限レベルの変更
+permission-server-acl = サーバー ACL の変更
+
+## Moderation reasons
+
+reason-invite-revoked = 招待を取り消しました
+reason-captcha-answer = キャプチャの回答
+reason-quarantine = キャプチャにまだ回答していません
+violation-spam = スパム
+violation-likely-bot = キャプチャに失敗しました
+violation-impersonation = ルームメンバーのなりすまし
+violation-offensive-display-name = 不適切な表示名
+violation-invite-spam = 招待が多すぎます
+violation-policy-list = ポリシーリストによる禁止
@@ -0,0 +1,73 @@
+## Captcha
+
+captcha-question = { $name }：{ $question }
+captcha-reply-hint = 請回覆此訊息作答。
+captcha-puzzle-add = { $a } + { $b } 等於多少？
+captcha-puzzle-subtract = { $a } - { $b } 等於多少？
+captcha-puzzle-multiply = { $a } × { $b } 等於多少？
+captcha-puzzle-reverse = 請將單字「{ $word }」倒著拼寫後回覆。
+captcha-puzzle-count = 單字「{ $word }」有幾個字母？
+captcha-puzzle-image = 請輸入圖片中顯示的字元。
+
+## Welcome
+
+welcome-rules-hint = 請以 ✅ 回應表示同意規則。
+
+## Commands
+
+command-usage =
+    指令：
+    !t1 vouch <使用者 ID>
+    !t1 acl add <伺服器>
+    !t1 acl remove <伺服器>
+    !t1 pardon <使用者 ID>
+    !t1 history [使用者 ID] [聊天室 ID] [期間，例如 7d]
+command-vouched = 已為 { $user } 擔保。
+command-invalid-user = { $user } 不是有效的使用者 ID。
+command-invalid-server = { $server } 不是有效的伺服器名稱。
+command-acl-added = 已在受保護的聊天室拒絕 { $server }。
+command-acl-allowlisted = { $server } 在允許清單中，無法拒絕。
+command-acl-removed = 已從受保護聊天室的拒絕清單移除 { $server }。
+command-pardoned = 已赦免 { $user }，並在 { $count } 個聊天室解除封鎖。
+command-admin-only = 只有機器人管理員可以執行此指令。
+command-history-empty = 沒有違規紀錄。
+
+## Appeals
+
+appeal-forwarded = 你的申訴已轉交給管理員。
+appeal-pending = 你的申訴正在等待管理員處理。
+appeal-not-banned = 你沒有被此機器人保護的任何聊天室封鎖。
+appeal-accepted = 你的申訴已被接受，可以重新加入聊天室。
+
+## Log room
+
+log-missing-power = { $room } 缺少權限：{ $permissions }。在授予權限之前，將略過這些操作。
+log-power-restored = { $room } 已再次授予所有權限。
+log-invited-join = { $user } 受 { $inviter } 邀請加入了 { $room }
+log-report-kick = 原本會將 { $user } 踢出 { $room }：{ $reason }
+log-report-ban = 原本會將 { $user } 從 { $room } 封鎖：{ $reason }
+log-report-revoke-invite = 原本會撤銷 { $user } 加入 { $room } 的邀請
+log-report-redact = 原本會刪除 { $user } 在 { $room } 的訊息：{ $reason }
+log-report-quarantine = 原本會在 { $room } 限制 { $user }：{ $reason }
+log-report-server-acl = 原本會在 { $room } 拒絕 { $server }
+log-appeal = { $user } 對在 { $rooms } 的封鎖提出申訴：{ $message }
+log-appeal-history = 違規紀錄：
+log-appeal-hint = 以 ✅ 回應即可解除封鎖。
+log-pardoned = { $moderator } 已赦免 { $user }，並在 { $count } 個聊天室解除封鎖。
+permission-kick = 踢出
+permission-ban = 封鎖
+permission-redact = 刪除訊息
+permission-power-levels = 變更權限等級
+permission-server-acl = 變更伺服器 ACL
+
+## Moderation reasons
+
+reason-invite-revoked = 已撤銷邀請
+reason-captcha-answer = 驗證問題的回答
+reason-quarantine = 尚未回答驗證問題
+violation-spam = 垃圾訊息
+violation-likely-bot = 未通過驗證
+violation-impersonation = 冒充聊天室成員
+violation-offensive-display-name = 不當的顯示名稱
+violation-invite-spam = 邀請次數過多
+violation-policy-list = 被政策清單封鎖
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use matrix_sdk::{
+    ruma::{
+        events::{
+            reaction::{ReactionEventContent, SyncReactionEvent},
+            relation::Annotation,
+            room::{member::MembershipState, message::RoomMessageEventContent},
+        },
+        OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId,
+    },
+    Client, Room,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef, RpcReplyPort};
+use tracing::{error, info};
+
+use crate::{config::T1Config, i18n::tr, ledger::HistoryFilter, matrix::UserRoomId};
+
+use super::{
+    config_provider::ConfigProviderMessage,
+    moderator::ModeratorMessage,
+    reputation::{self, ReputationEvent},
+    server_acl::ServerAclMessage,
+};
+
+/// Reaction moderators use to accept an appeal
+const ACCEPT_KEY: &str = "✅";
+
+/// Number of past violations shown with an appeal
+const APPEAL_HISTORY_LIMIT: u32 = 10;
+
+/// Forwards the appeals of banned users to the log room and pardons them.
+pub(crate) struct Appeal;
+
+pub(crate) enum AppealMessage {
+    /// A message sent to the bot in a DM
+    DirectMessage {
+        user_room_id: UserRoomId,
+        body: String,
+    },
+    /// A reaction to a message in the log room
+    Reaction {
+        room_id: OwnedRoomId,
+        ev: Box<SyncReactionEvent>,
+    },
+    /// Unbans the user from every protected room. Replies the number of rooms
+    /// the user was unbanned from.
+    Pardon(OwnedUserId, RpcReplyPort<usize>),
+}
+
+struct PendingAppeal {
+    user_id: OwnedUserId,
+    /// DM where the user appealed
+    dm_room_id: OwnedRoomId,
+}
+
+pub(crate) struct AppealState {
+    client: Client,
+    /// Appeals waiting for a moderator, by the event ID of their message in
+    /// the log room
+    pending: HashMap<OwnedEventId, PendingAppeal>,
+}
+
+async fn get_config() -> Result<Option<T1Config>, ActorProcessingErr> {
+    if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+        return Ok(Some(ractor::call!(
+            config_provider,
+            ConfigProviderMessage::GetConfig
+        )?));
+    }
+    Ok(None)
+}
+
+/// Protected rooms the user is banned from
+async fn banned_rooms(
+    client: &Client,
+    config: &T1Config,
+    user_id: &UserId,
+) -> Result<Vec<Room>, ActorProcessingErr> {
+    let mut rooms = vec![];
+    for room in client.joined_rooms() {
+        if config.is_room_enabled(room.room_id().as_str())
+            && let Some(member) = room.get_member_no_sync(user_id).await?
+            && *member.membership() == MembershipState::Ban
+        {
+            rooms.push(room);
+        }
+    }
+    Ok(rooms)
+}
+
+async fn reply(
+    client: &Client,
+    config: &T1Config,
+    room_id: &RoomId,
+    id: &str,
+) -> Result<(), ActorProcessingErr> {
+    if let Some(room) = client.get_room(room_id) {
+        let text = tr(config.locale(room_id.as_str()), id, []);
+        room.send(RoomMessageEventContent::notice_plain(text))
+            .await?;
+    }
+    Ok(())
+}
+
+async fn forward_appeal(
+    state: &mut AppealState,
+    user_room_id: UserRoomId,
+    body: String,
+) -> Result<(), ActorProcessingErr> {
+    let Some(config) = get_config().await? else {
+        return Ok(());
+    };
+    let (Some(_), Some(log_room_id)) = (&config.appeals, &config.log_room) else {
+        return Ok(());
+    };
+    let Some(log_room) = state.client.get_room(&RoomId::parse(log_room_id)?) else {
+        return Ok(());
+    };
+    let dm_room_id = &user_room_id.room_id;
+    if state
+        .pending
+        .values()
+        .any(|appeal| appeal.user_id == user_room_id.user_id)
+    {
+        return reply(&state.client, &config, dm_room_id, "appeal-pending").await;
+    }
+    let rooms = banned_rooms(&state.client, &config, &user_room_id.user_id).await?;
+    if rooms.is_empty() {
+        return reply(&state.client, &config, dm_room_id, "appeal-not-banned").await;
+    }
+
+    let mut history = vec![];
+    if let Some(moderator) = ActorRef::<ModeratorMessage>::where_is("moderator".into()) {
+        let filter = HistoryFilter {
+            user_id: Some(user_room_id.user_id.to_string()),
+            limit: Some(APPEAL_HISTORY_LIMIT),
+            ..Default::default()
+        };
+        history = ractor::call!(moderator, ModeratorMessage::History, filter)?;
+    }
+    let locale = config.locale(log_room_id);
+    let rooms = rooms
+        .iter()
+        .map(|room| room.room_id().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut lines = vec![tr(
+        locale,
+        "log-appeal",
+        [
+            ("user", user_room_id.user_id.to_string().into()),
+            ("rooms", rooms.into()),
+            ("message", body.into()),
+        ],
+    )];
+    if !history.is_empty() {
+        lines.push(tr(locale, "log-appeal-history", []));
+        lines.extend(history.iter().map(ToString::to_string));
+    }
+    lines.push(tr(locale, "log-appeal-hint", []));
+
+    info!(user = %user_room_id, "forwarding appeal to the log room");
+    let response = log_room
+        .send(RoomMessageEventContent::notice_plain(lines.join("\n")))
+        .await?;
+    log_room
+        .send(ReactionEventContent::new(Annotation::new(
+            response.event_id.clone(),
+            ACCEPT_KEY.to_string(),
+        )))
+        .await?;
+    state.pending.insert(
+        response.event_id,
+        PendingAppeal {
+            user_id: user_room_id.user_id,
+            dm_room_id: dm_room_id.clone(),
+        },
+    );
+    reply(&state.client, &config, dm_room_id, "appeal-forwarded").await
+}
+
+/// Unbans the user from every protected room, forgets their violations and,
+/// if configured, vouches for them. Returns the number of rooms the user was
+/// unbanned from.
+async fn pardon(state: &mut AppealState, user_id: &UserId) -> Result<usize, ActorProcessingErr> {
+    let Some(config) = get_config().await? else {
+        return Ok(0);
+    };
+    let mut unbanned = 0;
+    for room in banned_rooms(&state.client, &config, user_id).await? {
+        info!(user = %user_id, room = %room.room_id(), "unbanning pardoned user");
+        match room.unban_user(user_id, None).await {
+            Ok(()) => unbanned += 1,
+            Err(error) => {
+                error!(user = %user_id, room = %room.room_id(), ?error, "Unable to unban")
+            }
+        }
+    }
+    reputation::record(user_id, ReputationEvent::Pardon)?;
+    if config.appeals.as_ref().is_some_and(|appeals| appeals.vouch) {
+        reputation::record(user_id, ReputationEvent::Vouch)?;
+    }
+    if let Some(server_acl) = ActorRef::where_is("server_acl".into()) {
+        ractor::cast!(server_acl, ServerAclMessage::Pardon(user_id.to_owned()))?;
+    }
+
+    let accepted: Vec<OwnedEventId> = state
+        .pending
+        .iter()
+        .filter(|(_, appeal)| appeal.user_id == user_id)
+        .map(|(event_id, _)| event_id.clone())
+        .collect();
+    for event_id in accepted {
+        if let Some(appeal) = state.pending.remove(&event_id) {
+            reply(
+                &state.client,
+                &config,
+                &appeal.dm_room_id,
+                "appeal-accepted",
+            )
+            .await?;
+        }
+    }
+    Ok(unbanned)
+}
+
+impl Actor for Appeal {
+    type Msg = AppealMessage;
+    type State = AppealState;
+    type Arguments = Client;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(AppealState {
+            client: args,
+            pending: HashMap::new(),
+        })
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            AppealMessage::DirectMessage { user_room_id, body } => {
+                forward_appeal(state, user_room_id, body).await?;
+            }
+            AppealMessage::Reaction { room_id, ev } => {
+                let Some(ev) = ev.as_original() else {
+                    return Ok(());
+                };
+                let annotation = &ev.content.relates_to;
+                let Some(appeal) = state.pending.get(&annotation.event_id) else {
+                    return Ok(());
+                };
+                if annotation.key != ACCEPT_KEY {
+                    return Ok(());
+                }
+                let Some(config) = get_config().await? else {
+                    return Ok(());
+                };
+                // Appeals are only accepted in the log room, by admins or by
+                // moderators who could ban the user themselves
+                if config.log_room.as_deref() != Some(room_id.as_str()) {
+                    return Ok(());
+                }
+                let Some(room) = state.client.get_room(&room_id) else {
+                    return Ok(());
+                };
+                let is_moderator = room
+                    .get_member(&ev.sender)
+                    .await?
+                    .is_some_and(|member| member.can_ban());
+                if !is_moderator && !config.is_admin(ev.sender.as_str()) {
+                    return Ok(());
+                }
+                let user_id = appeal.user_id.clone();
+                info!(user = %user_id, moderator = %ev.sender, "appeal accepted");
+                let count = pardon(state, &user_id).await?;
+                let text = tr(
+                    config.locale(room_id.as_str()),
+                    "log-pardoned",
+                    [
+                        ("user", user_id.to_string().into()),
+                        ("moderator", ev.sender.to_string().into()),
+                        ("count", count.into()),
+                    ],
+                );
+                room.send(RoomMessageEventContent::notice_plain(text))
+                    .await?;
+            }
+            AppealMessage::Pardon(user_id, reply) => {
+                let count = pardon(state, &user_id).await?;
+                reply.send(count)?;
+            }
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use matrix_sdk::{
+    ruma::{OwnedRoomId, OwnedUserId},
+    Client,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use tracing::info;
+
+use super::config_provider::ConfigProviderMessage;
+
+/// Accepts room invites from the configured admins and rejects the others.
+/// DM invites are accepted when appeals are enabled.
+pub(crate) struct AutoJoin;
+
+pub(crate) enum AutoJoinMessage {
+    /// The bot was invited to a room
+    Invited {
+        room_id: OwnedRoomId,
+        inviter: OwnedUserId,
+        /// The invite is for a DM rather than a room to protect
+        is_direct: bool,
+    },
+}
+
+impl Actor for AutoJoin {
+    type Msg = AutoJoinMessage;
+    type State = Client;
+    type Arguments = Client;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(args)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            AutoJoinMessage::Invited {
+                room_id,
+                inviter,
+                is_direct,
+            } => {
+                let Some(config_provider) = ActorRef::where_is("config_provider".into()) else {
+                    return Ok(());
+                };
+                let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+                let Some(room) = state.get_room(&room_id) else {
+                    return Ok(());
+                };
+                if is_direct {
+                    // Banned users appeal in a DM, other DMs are left pending
+                    if config.appeals.is_some() {
+                        info!(room = %room_id, inviter = %inviter, "accepting DM invite");
+                        room.join().await?;
+                    }
+                    return Ok(());
+                }
+                let allowed = config.auto_join.is_some_and(|auto_join| {
+                    auto_join.is_allowed(inviter.as_str(), inviter.server_name().as_str())
+                });
+                if allowed {
+                    info!(room = %room_id, inviter = %inviter, "accepting room invite");
+                    room.join().await?;
+                    ractor::cast!(
+                        config_provider,
+                        ConfigProviderMessage::AddManagedRoom(room_id.to_string())
+                    )?;
+                } else {
+                    info!(room = %room_id, inviter = %inviter, "rejecting room invite");
+                    room.leave().await?;
+                }
+            }
+        };
+        Ok(())
+    }
+}
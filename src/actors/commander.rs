@@ -0,0 +1,266 @@
+use matrix_sdk::{
+    ruma::{events::room::message::RoomMessageEventContent, RoomId, ServerName, UserId},
+    Client, Room,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use tracing::info;
+
+use crate::{
+    i18n::tr,
+    ledger::{now_secs, parse_duration, HistoryFilter},
+    matrix::UserRoomId,
+};
+
+use super::{
+    appeal::AppealMessage,
+    config_provider::ConfigProviderMessage,
+    moderator::ModeratorMessage,
+    reputation::{self, ReputationEvent},
+    server_acl::ServerAclMessage,
+};
+
+/// Prefix of the commands moderators send to the bot
+pub(crate) const COMMAND_PREFIX: &str = "!t1";
+
+/// Runs commands sent by moderators of the protected rooms.
+pub(crate) struct Commander;
+
+pub(crate) enum CommanderMessage {
+    /// A message starting with `COMMAND_PREFIX`, sent by the user to the room
+    Command {
+        user_room_id: UserRoomId,
+        body: String,
+    },
+}
+
+async fn reply(room: &Room, text: String) -> Result<(), ActorProcessingErr> {
+    room.send(RoomMessageEventContent::notice_plain(text))
+        .await?;
+    Ok(())
+}
+
+/// Number of violations listed by the history command
+const HISTORY_LIMIT: u32 = 20;
+
+/// Parses the arguments of the history command: a user ID, a room ID and how
+/// long ago to start from, in any order.
+fn history_filter(args: &[&str]) -> Option<HistoryFilter> {
+    let mut filter = HistoryFilter {
+        limit: Some(HISTORY_LIMIT),
+        ..Default::default()
+    };
+    for arg in args {
+        if UserId::parse(*arg).is_ok() {
+            filter.user_id = Some(arg.to_string());
+        } else if RoomId::parse(*arg).is_ok() {
+            filter.room_id = Some(arg.to_string());
+        } else {
+            filter.since_secs = Some(now_secs() - parse_duration(arg)?);
+        }
+    }
+    Some(filter)
+}
+
+impl Actor for Commander {
+    type Msg = CommanderMessage;
+    type State = Client;
+    type Arguments = Client;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(args)
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            CommanderMessage::Command { user_room_id, body } => {
+                let Some(room) = state.get_room(&user_room_id.room_id) else {
+                    return Ok(());
+                };
+                let Some(config_provider) = ActorRef::where_is("config_provider".into()) else {
+                    return Ok(());
+                };
+                let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+                // The sender of a DM or of an unprotected room may hold any
+                // power there, commands are only taken from protected rooms
+                // and the log room
+                let room_id = user_room_id.room_id.as_str();
+                let is_log_room = config.log_room.as_deref() == Some(room_id);
+                if room.is_direct().await? || !(is_log_room || config.is_protected_room(room_id)) {
+                    return Ok(());
+                }
+                let is_admin = config.is_admin(user_room_id.user_id.as_str());
+                // Moderators who could ban the user themselves in the protected
+                // room may run the commands scoped to it
+                let is_moderator = !is_log_room
+                    && room
+                        .get_member(&user_room_id.user_id)
+                        .await?
+                        .is_some_and(|member| member.can_ban());
+                if !is_admin && !is_moderator {
+                    return Ok(());
+                }
+                let locale = config.locale(room_id).to_string();
+                let args: Vec<&str> = body.split_whitespace().skip(1).collect();
+                info!(user = %user_room_id, command = ?args, "received command");
+                match args.as_slice() {
+                    // Trust applies in every room and skips the captcha
+                    ["vouch", _] if !is_admin => {
+                        reply(&room, tr(&locale, "command-admin-only", [])).await?;
+                    }
+                    ["vouch", user_id] => match UserId::parse(*user_id) {
+                        Ok(user_id) => {
+                            reputation::record(&user_id, ReputationEvent::Vouch)?;
+                            let text = tr(
+                                &locale,
+                                "command-vouched",
+                                [("user", user_id.as_str().into())],
+                            );
+                            reply(&room, text).await?;
+                        }
+                        Err(_) => {
+                            let text = tr(
+                                &locale,
+                                "command-invalid-user",
+                                [("user", (*user_id).into())],
+                            );
+                            reply(&room, text).await?;
+                        }
+                    },
+                    // The server ACL applies to every protected room
+                    ["acl", "add" | "remove", _] if !is_admin => {
+                        reply(&room, tr(&locale, "command-admin-only", [])).await?;
+                    }
+                    ["acl", action @ ("add" | "remove"), server_name] => {
+                        let Ok(server_name) = ServerName::parse(*server_name) else {
+                            let text = tr(
+                                &locale,
+                                "command-invalid-server",
+                                [("server", (*server_name).into())],
+                            );
+                            return reply(&room, text).await;
+                        };
+                        let Some(server_acl) =
+                            ActorRef::<ServerAclMessage>::where_is("server_acl".into())
+                        else {
+                            return Ok(());
+                        };
+                        let id = if *action == "add" {
+                            let denied = ractor::call!(
+                                server_acl,
+                                ServerAclMessage::Deny,
+                                server_name.clone()
+                            )?;
+                            if denied {
+                                "command-acl-added"
+                            } else {
+                                "command-acl-allowlisted"
+                            }
+                        } else {
+                            ractor::call!(
+                                server_acl,
+                                ServerAclMessage::Remove,
+                                server_name.clone()
+                            )?;
+                            "command-acl-removed"
+                        };
+                        let text = tr(&locale, id, [("server", server_name.as_str().into())]);
+                        reply(&room, text).await?;
+                    }
+                    ["pardon", _] if !is_admin => {
+                        reply(&room, tr(&locale, "command-admin-only", [])).await?;
+                    }
+                    ["pardon", user_id] => {
+                        let Ok(user_id) = UserId::parse(*user_id) else {
+                            let text = tr(
+                                &locale,
+                                "command-invalid-user",
+                                [("user", (*user_id).into())],
+                            );
+                            return reply(&room, text).await;
+                        };
+                        let Some(appeal) = ActorRef::<AppealMessage>::where_is("appeal".into())
+                        else {
+                            return Ok(());
+                        };
+                        let count = ractor::call!(appeal, AppealMessage::Pardon, user_id.clone())?;
+                        let text = tr(
+                            &locale,
+                            "command-pardoned",
+                            [("user", user_id.as_str().into()), ("count", count.into())],
+                        );
+                        reply(&room, text).await?;
+                    }
+                    ["history", args @ ..] => {
+                        let Some(mut filter) = history_filter(args) else {
+                            return reply(&room, tr(&locale, "command-usage", [])).await;
+                        };
+                        // Moderators only see the violations in their room
+                        if !is_admin {
+                            if filter.room_id.as_deref().is_some_and(|id| id != room_id) {
+                                return reply(&room, tr(&locale, "command-admin-only", [])).await;
+                            }
+                            filter.room_id = Some(room_id.to_string());
+                        }
+                        let Some(moderator) =
+                            ActorRef::<ModeratorMessage>::where_is("moderator".into())
+                        else {
+                            return Ok(());
+                        };
+                        let records = ractor::call!(moderator, ModeratorMessage::History, filter)?;
+                        let text = if records.is_empty() {
+                            tr(&locale, "command-history-empty", [])
+                        } else {
+                            records
+                                .iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<_>>()
+                                .join("\n")
+                        };
+                        reply(&room, text).await?;
+                    }
+                    _ => reply(&room, tr(&locale, "command-usage", [])).await?,
+                }
+            }
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_filter_defaults() {
+        let filter = history_filter(&[]).unwrap();
+        assert_eq!(filter.user_id, None);
+        assert_eq!(filter.room_id, None);
+        assert_eq!(filter.since_secs, None);
+        assert_eq!(filter.limit, Some(HISTORY_LIMIT));
+    }
+
+    #[test]
+    fn history_filter_any_order() {
+        let before = now_secs();
+        let filter = history_filter(&["2h", "!room:example.org", "@spam:example.org"]).unwrap();
+        assert_eq!(filter.user_id.as_deref(), Some("@spam:example.org"));
+        assert_eq!(filter.room_id.as_deref(), Some("!room:example.org"));
+        let since = filter.since_secs.unwrap();
+        assert!(since >= before - 2 * 60 * 60 && since <= now_secs() - 2 * 60 * 60);
+    }
+
+    #[test]
+    fn history_filter_rejects_unknown_arguments() {
+        assert!(history_filter(&["spam"]).is_none());
+        assert!(history_filter(&["@spam:example.org", "soon"]).is_none());
+    }
+}
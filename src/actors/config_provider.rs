@@ -1,6 +1,13 @@
-use std::{fs, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
-use ractor::{Actor, RpcReplyPort};
+use matrix_sdk::{ruma::RoomAliasId, Client};
+use ractor::{Actor, ActorProcessingErr, RpcReplyPort};
+use tracing::{info, warn};
 
 use crate::config::T1Config;
 
@@ -8,19 +15,142 @@ pub(crate) struct ConfigProvider;
 
 pub(crate) enum ConfigProviderMessage {
     GetConfig(RpcReplyPort<T1Config>),
+    /// Replaces the child rooms discovered in a space
+    SetSpaceChildren {
+        space: String,
+        children: Vec<String>,
+    },
+    /// Persists a room the bot joined on invite
+    AddManagedRoom(String),
+    /// The new room replaces the upgraded room and takes over its config
+    SetRoomReplacement {
+        old: String,
+        new: String,
+    },
+}
+
+pub(crate) struct ConfigProviderState {
+    config_path: PathBuf,
+    client: Client,
+    /// Room ID of every alias used as a room key, resolved once
+    room_aliases: HashMap<String, String>,
+    /// When each unresolvable alias may be retried, and the delay before the
+    /// retry after that
+    alias_retries: HashMap<String, (Instant, Duration)>,
+    /// Space each discovered child room belongs to
+    space_children: HashMap<String, String>,
+    /// Room each upgraded room replaces
+    room_replacements: HashMap<String, String>,
+    managed_rooms: Vec<String>,
+}
+
+/// Delay before retrying an alias that failed to resolve, doubled on every
+/// failure up to `MAX_ALIAS_RETRY`
+const MIN_ALIAS_RETRY: Duration = Duration::from_secs(60);
+const MAX_ALIAS_RETRY: Duration = Duration::from_secs(60 * 60);
+
+const MANAGED_ROOMS_KEY: &[u8] = b"t1bot.managed_rooms";
+const SPACE_CHILDREN_KEY: &[u8] = b"t1bot.space_children";
+
+fn read_config(state: &ConfigProviderState) -> Result<T1Config, ActorProcessingErr> {
+    let config_text = fs::read_to_string(&state.config_path)?;
+    let config: T1Config = toml::from_str(&config_text)?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Resolves the alias keys of the config that were not resolved yet. Aliases
+/// only need resolving on start and when the config gains new ones, failures
+/// are retried with a backoff so that `GetConfig` does not wait on the network
+/// for every message.
+async fn resolve_aliases(state: &mut ConfigProviderState, config: &T1Config) {
+    for key in config.rooms.keys() {
+        if state.room_aliases.contains_key(key)
+            || state
+                .alias_retries
+                .get(key)
+                .is_some_and(|(retry_at, _)| Instant::now() < *retry_at)
+        {
+            continue;
+        }
+        let Ok(alias) = RoomAliasId::parse(key) else {
+            continue;
+        };
+        match state.client.resolve_room_alias(&alias).await {
+            Ok(response) => {
+                info!(alias = key, room = %response.room_id, "resolved room alias");
+                state.alias_retries.remove(key);
+                state
+                    .room_aliases
+                    .insert(key.clone(), response.room_id.to_string());
+            }
+            Err(error) => {
+                let delay = state
+                    .alias_retries
+                    .get(key)
+                    .map_or(MIN_ALIAS_RETRY, |(_, delay)| {
+                        (*delay * 2).min(MAX_ALIAS_RETRY)
+                    });
+                warn!(alias = key, ?error, ?delay, "Unable to resolve room alias");
+                state
+                    .alias_retries
+                    .insert(key.clone(), (Instant::now() + delay, delay));
+            }
+        }
+    }
 }
 
 impl Actor for ConfigProvider {
     type Msg = ConfigProviderMessage;
-    type State = PathBuf;
-    type Arguments = PathBuf;
+    type State = ConfigProviderState;
+    type Arguments = (PathBuf, Client);
 
     async fn pre_start(
         &self,
         _myself: ractor::ActorRef<Self::Msg>,
         args: Self::Arguments,
     ) -> Result<Self::State, ractor::ActorProcessingErr> {
-        Ok(args)
+        let (config_path, client) = args;
+        Ok(ConfigProviderState {
+            config_path,
+            client,
+            room_aliases: HashMap::new(),
+            alias_retries: HashMap::new(),
+            space_children: HashMap::new(),
+            room_replacements: HashMap::new(),
+            managed_rooms: vec![],
+        })
+    }
+
+    async fn post_start(
+        &self,
+        _myself: ractor::ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ractor::ActorProcessingErr> {
+        if let Some(value) = state
+            .client
+            .state_store()
+            .get_custom_value(MANAGED_ROOMS_KEY)
+            .await?
+        {
+            state.managed_rooms = serde_json::from_slice(&value)?;
+        }
+        // Kept across restarts so that the children stay protected until the
+        // space actor refreshes them
+        if let Some(value) = state
+            .client
+            .state_store()
+            .get_custom_value(SPACE_CHILDREN_KEY)
+            .await?
+        {
+            state.space_children = serde_json::from_slice(&value)?;
+        }
+        // A broken config is reported by `GetConfig`, not by restarting
+        match read_config(state) {
+            Ok(config) => resolve_aliases(state, &config).await,
+            Err(error) => warn!(?error, "Unable to read the config"),
+        }
+        Ok(())
     }
 
     async fn handle(
@@ -31,10 +161,40 @@ impl Actor for ConfigProvider {
     ) -> Result<(), ractor::ActorProcessingErr> {
         match message {
             ConfigProviderMessage::GetConfig(reply) => {
-                let config_text = fs::read_to_string(state)?;
-                let config: T1Config = toml::from_str(&config_text)?;
+                let mut config = read_config(state)?;
+                resolve_aliases(state, &config).await;
+                config.resolve_aliases(state.room_aliases.clone());
+                config.space_children = state.space_children.clone();
+                config.room_replacements = state.room_replacements.clone();
+                config.managed_rooms = state.managed_rooms.clone();
                 reply.send(config)?;
             }
+            ConfigProviderMessage::SetSpaceChildren { space, children } => {
+                state.space_children.retain(|_, parent| *parent != space);
+                for child in children {
+                    state.space_children.insert(child, space.clone());
+                }
+                let value = serde_json::to_vec(&state.space_children)?;
+                state
+                    .client
+                    .state_store()
+                    .set_custom_value(SPACE_CHILDREN_KEY, value)
+                    .await?;
+            }
+            ConfigProviderMessage::AddManagedRoom(room_id) => {
+                if !state.managed_rooms.contains(&room_id) {
+                    state.managed_rooms.push(room_id);
+                    let value = serde_json::to_vec(&state.managed_rooms)?;
+                    state
+                        .client
+                        .state_store()
+                        .set_custom_value(MANAGED_ROOMS_KEY, value)
+                        .await?;
+                }
+            }
+            ConfigProviderMessage::SetRoomReplacement { old, new } => {
+                state.room_replacements.insert(new, old);
+            }
         };
         Ok(())
     }
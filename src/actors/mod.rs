@@ -1,5 +1,13 @@
+pub(crate) mod appeal;
+pub(crate) mod auto_join;
+pub(crate) mod commander;
 pub(crate) mod config_provider;
 pub(crate) mod moderator;
 pub(crate) mod monitor;
+pub(crate) mod policy_list;
+pub(crate) mod reputation;
+pub(crate) mod room_upgrade;
+pub(crate) mod server_acl;
+pub(crate) mod space;
 pub(crate) mod spawner;
 pub(crate) mod supervisor;
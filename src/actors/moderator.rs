@@ -1,28 +1,430 @@
-use matrix_sdk::Client;
-use ractor::{Actor, ActorProcessingErr, ActorRef};
-use tracing::info;
+use std::collections::HashMap;
 
-use crate::matrix::UserRoomId;
+use fluent_bundle::FluentValue;
+use matrix_sdk::{
+    ruma::{
+        events::{
+            room::{message::RoomMessageEventContent, power_levels::RoomPowerLevels},
+            StateEventType,
+        },
+        OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, UserId,
+    },
+    Client, Room,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef, RpcReplyPort};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{Mode, Quarantine, Sanction, T1Config},
+    i18n::{tr, DEFAULT_LOCALE},
+    ledger::{now_secs, HistoryFilter, Ledger, Outcome, ViolationRecord},
+    matrix::UserRoomId,
+};
+
+use super::{
+    config_provider::ConfigProviderMessage,
+    policy_list::PolicyListMessage,
+    reputation::{self, ReputationEvent},
+    server_acl::ServerAclMessage,
+};
 
 #[derive(Debug)]
 pub(crate) enum ViolationKind {
     Spam,
     LikelyBot,
+    Impersonation,
+    OffensiveDisplayName,
+    InviteSpam,
+    /// Banned by a subscribed policy list
+    PolicyList,
+}
+
+impl ViolationKind {
+    /// Identifier of the reason shown to the user in the message catalog
+    fn message_id(&self) -> &'static str {
+        match self {
+            ViolationKind::Spam => "violation-spam",
+            ViolationKind::LikelyBot => "violation-likely-bot",
+            ViolationKind::Impersonation => "violation-impersonation",
+            ViolationKind::OffensiveDisplayName => "violation-offensive-display-name",
+            ViolationKind::InviteSpam => "violation-invite-spam",
+            ViolationKind::PolicyList => "violation-policy-list",
+        }
+    }
+
+    /// Name of the kind in the violation ledger
+    pub(crate) fn name(&self) -> &'static str {
+        self.message_id().trim_start_matches("violation-")
+    }
 }
 
 // TODO user real user_id and room_id type
 pub(crate) enum ModeratorMessage {
     Violation {
         user_room_id: UserRoomId,
+        /// Name of the monitor that found the violation
+        monitor: &'static str,
         kind: ViolationKind,
+        /// Events that caused the violation
+        evidence: Vec<OwnedEventId>,
+        sanction: Sanction,
     },
+    RevokeInvite {
+        user_room_id: UserRoomId,
+        inviter: OwnedUserId,
+    },
+    Redact {
+        user_room_id: UserRoomId,
+        event_id: OwnedEventId,
+        /// Identifier of the reason in the message catalog
+        reason: &'static str,
+    },
+    /// Bans the user without recording a violation, for bans carried over
+    /// from an upgraded room
+    Ban {
+        user_room_id: UserRoomId,
+        reason: String,
+    },
+    /// Checks the bot has the power needed in the room after joining it or
+    /// after its power levels changed
+    CheckPermissions(OwnedRoomId),
+    /// Returns the recorded violations matching the filter
+    History(HistoryFilter, RpcReplyPort<Vec<ViolationRecord>>),
 }
 
 pub(crate) struct Moderator;
 
+pub(crate) struct ModeratorState {
+    client: Client,
+    /// Permissions the bot lacks in each room, last reported to the log room
+    missing_permissions: HashMap<OwnedRoomId, Vec<Permission>>,
+    /// Absent if the ledger could not be opened
+    ledger: Option<Ledger>,
+}
+
+/// Power the bot needs for the actions configured in a room
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Permission {
+    Kick,
+    Ban,
+    Redact,
+    /// Needed by the power level quarantine
+    PowerLevels,
+    /// Needed by the server ACL
+    ServerAcl,
+}
+
+impl Permission {
+    fn message_id(&self) -> &'static str {
+        match self {
+            Permission::Kick => "permission-kick",
+            Permission::Ban => "permission-ban",
+            Permission::Redact => "permission-redact",
+            Permission::PowerLevels => "permission-power-levels",
+            Permission::ServerAcl => "permission-server-acl",
+        }
+    }
+
+    fn is_granted(&self, power_levels: &RoomPowerLevels, user_id: &UserId) -> bool {
+        match self {
+            Permission::Kick => power_levels.user_can_kick(user_id),
+            Permission::Ban => power_levels.user_can_ban(user_id),
+            Permission::Redact => power_levels.user_can_redact_event_of_other(user_id),
+            Permission::PowerLevels => {
+                power_levels.user_can_send_state(user_id, StateEventType::RoomPowerLevels)
+            }
+            Permission::ServerAcl => {
+                power_levels.user_can_send_state(user_id, StateEventType::RoomServerAcl)
+            }
+        }
+    }
+}
+
+fn required_permissions(config: &T1Config, room_id: &str) -> Vec<Permission> {
+    let mut permissions = vec![Permission::Kick, Permission::Ban, Permission::Redact];
+    if config
+        .monitor(room_id, |m| &m.captcha)
+        .and_then(|captcha| captcha.quarantine)
+        == Some(Quarantine::PowerLevel)
+    {
+        permissions.push(Permission::PowerLevels);
+    }
+    if config.server_acl.is_some() {
+        permissions.push(Permission::ServerAcl);
+    }
+    permissions
+}
+
+/// Returns whether the bot has the permission in the room. Actions the bot
+/// lacks the power for are skipped instead of failing the moderator.
+async fn is_granted(
+    client: &Client,
+    room: &Room,
+    permission: Permission,
+) -> Result<bool, ActorProcessingErr> {
+    let Some(user_id) = client.user_id() else {
+        return Ok(false);
+    };
+    let granted = permission.is_granted(&room.power_levels().await?, user_id);
+    if !granted {
+        warn!(room = %room.room_id(), ?permission, "missing permission, skipping action");
+    }
+    Ok(granted)
+}
+
+/// Posts the message from the catalog to the log room, if one is configured.
+pub(crate) async fn notify_log_room<'a>(
+    client: &Client,
+    id: &str,
+    args: impl IntoIterator<Item = (&'a str, FluentValue<'a>)>,
+) -> Result<(), ActorProcessingErr> {
+    let Some(config_provider) = ActorRef::where_is("config_provider".into()) else {
+        return Ok(());
+    };
+    let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+    if let Some(log_room) = &config.log_room
+        && let Some(room) = client.get_room(&RoomId::parse(log_room)?)
+    {
+        let text = tr(config.locale(log_room), id, args);
+        room.send(RoomMessageEventContent::notice_plain(text))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Returns the outcome of the action if it is not enforced in the room. In
+/// report mode the action is logged and reported to the log room instead,
+/// `action` is the message of the report in the catalog. Every sanction and
+/// restriction of the bot in a protected room goes through this check, the
+/// captcha and welcome messages do not.
+pub(crate) async fn not_enforced_in<'a>(
+    client: &Client,
+    room_id: &RoomId,
+    action: &str,
+    args: impl IntoIterator<Item = (&'a str, FluentValue<'a>)>,
+) -> Result<Option<Outcome>, ActorProcessingErr> {
+    let Some(config_provider) = ActorRef::where_is("config_provider".into()) else {
+        return Ok(None);
+    };
+    let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+    match config.mode(room_id.as_str()) {
+        Mode::Enforce => Ok(None),
+        Mode::Report => {
+            info!(room = %room_id, action, "report mode, not acting");
+            notify_log_room(client, action, args).await?;
+            Ok(Some(Outcome::Reported))
+        }
+        Mode::Off => Ok(Some(Outcome::Ignored)),
+    }
+}
+
+/// Returns the outcome of the action against the user if it is not enforced
+/// in their room.
+pub(crate) async fn not_enforced(
+    client: &Client,
+    user_room_id: &UserRoomId,
+    action: &str,
+    reason: String,
+) -> Result<Option<Outcome>, ActorProcessingErr> {
+    not_enforced_in(
+        client,
+        &user_room_id.room_id,
+        action,
+        [
+            ("user", user_room_id.user_id.to_string().into()),
+            ("room", user_room_id.room_id.to_string().into()),
+            ("reason", reason.into()),
+        ],
+    )
+    .await
+}
+
+async fn check_permissions(
+    state: &mut ModeratorState,
+    room_id: OwnedRoomId,
+) -> Result<(), ActorProcessingErr> {
+    let Some(config_provider) = ActorRef::where_is("config_provider".into()) else {
+        return Ok(());
+    };
+    let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+    let (Some(room), Some(user_id)) = (state.client.get_room(&room_id), state.client.user_id())
+    else {
+        return Ok(());
+    };
+    // DMs, spaces and unlisted rooms are not moderated
+    if !config.is_protected_room(room_id.as_str()) || room.is_space() {
+        return Ok(());
+    }
+    let power_levels = room.power_levels().await?;
+    let missing: Vec<Permission> = required_permissions(&config, room_id.as_str())
+        .into_iter()
+        .filter(|permission| !permission.is_granted(&power_levels, user_id))
+        .collect();
+    let reported = state
+        .missing_permissions
+        .get(&room_id)
+        .cloned()
+        .unwrap_or_default();
+    if missing == reported {
+        return Ok(());
+    }
+    if missing.is_empty() {
+        info!(room = %room_id, "all permissions granted");
+        notify_log_room(
+            &state.client,
+            "log-power-restored",
+            [("room", room_id.to_string().into())],
+        )
+        .await?;
+    } else {
+        warn!(room = %room_id, ?missing, "missing permissions");
+        let locale = config.locale(config.log_room.as_deref().unwrap_or_default());
+        let permissions = missing
+            .iter()
+            .map(|permission| tr(locale, permission.message_id(), []))
+            .collect::<Vec<_>>()
+            .join(", ");
+        notify_log_room(
+            &state.client,
+            "log-missing-power",
+            [
+                ("room", room_id.to_string().into()),
+                ("permissions", permissions.into()),
+            ],
+        )
+        .await?;
+    }
+    state.missing_permissions.insert(room_id, missing);
+    Ok(())
+}
+
+async fn room_locale(user_room_id: &UserRoomId) -> Result<String, ActorProcessingErr> {
+    if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+        let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+        return Ok(config.locale(user_room_id.room_id.as_str()).to_string());
+    }
+    Ok(DEFAULT_LOCALE.to_string())
+}
+
+/// Rooms the ban of the user is propagated to
+async fn propagated_rooms(
+    user_room_id: &UserRoomId,
+) -> Result<Vec<OwnedRoomId>, ActorProcessingErr> {
+    if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+        let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+        return Ok(config
+            .ban_propagation_rooms(user_room_id.room_id.as_str())
+            .into_iter()
+            .filter_map(|room_id| RoomId::parse(room_id).ok())
+            .collect());
+    }
+    Ok(vec![])
+}
+
+/// Applies the sanction of the violation and returns its outcome.
+async fn sanction_user(
+    state: &ModeratorState,
+    user_room_id: &UserRoomId,
+    kind: &ViolationKind,
+    sanction: Sanction,
+) -> Result<Outcome, ActorProcessingErr> {
+    let action = match sanction {
+        Sanction::Kick => "log-report-kick",
+        Sanction::Ban => "log-report-ban",
+    };
+    let reason = tr(&room_locale(user_room_id).await?, kind.message_id(), []);
+    if let Some(outcome) = not_enforced(&state.client, user_room_id, action, reason.clone()).await?
+    {
+        return Ok(outcome);
+    }
+    reputation::record(&user_room_id.user_id, ReputationEvent::Violation)?;
+    if let Some(server_acl) = ActorRef::where_is("server_acl".into()) {
+        ractor::cast!(
+            server_acl,
+            ServerAclMessage::Violation(user_room_id.user_id.clone())
+        )?;
+    }
+    let Some(room) = state.client.get_room(&user_room_id.room_id) else {
+        return Ok(Outcome::Failed);
+    };
+    match sanction {
+        Sanction::Kick => {
+            if !is_granted(&state.client, &room, Permission::Kick).await? {
+                return Ok(Outcome::MissingPermission);
+            }
+            info!(
+                "Kicking user {} from {} for {:?}",
+                user_room_id.user_id, user_room_id.room_id, kind
+            );
+            room.kick_user(&user_room_id.user_id, Some(reason.as_str()))
+                .await?;
+            Ok(Outcome::Enforced)
+        }
+        Sanction::Ban => {
+            let propagated = propagated_rooms(user_room_id).await?;
+            info!(
+                propagated = ?propagated,
+                "Banning user {} from {} for {:?}",
+                user_room_id.user_id, user_room_id.room_id, kind
+            );
+            let outcome = if is_granted(&state.client, &room, Permission::Ban).await? {
+                room.ban_user(&user_room_id.user_id, Some(reason.as_str()))
+                    .await?;
+                Outcome::Enforced
+            } else {
+                Outcome::MissingPermission
+            };
+            for room_id in propagated {
+                let Some(room) = state.client.get_room(&room_id) else {
+                    continue;
+                };
+                if !is_granted(&state.client, &room, Permission::Ban).await? {
+                    continue;
+                }
+                let user_room_id = UserRoomId {
+                    user_id: user_room_id.user_id.clone(),
+                    room_id,
+                };
+                let reason = tr(&room_locale(&user_room_id).await?, kind.message_id(), []);
+                // Rooms in report or off mode keep their mode for propagated
+                // bans too
+                if not_enforced(&state.client, &user_room_id, action, reason.clone())
+                    .await?
+                    .is_some()
+                {
+                    continue;
+                }
+                // A failure in one room must not stop the others
+                if let Err(error) = room
+                    .ban_user(&user_room_id.user_id, Some(reason.as_str()))
+                    .await
+                {
+                    error!(user = %user_room_id, ?error, "Unable to propagate ban");
+                }
+            }
+            // Bans from policy lists are already published, bans that were
+            // not issued are not
+            if outcome == Outcome::Enforced
+                && !matches!(kind, ViolationKind::PolicyList)
+                && let Some(policy_list) = ActorRef::where_is("policy_list".into())
+            {
+                ractor::cast!(
+                    policy_list,
+                    PolicyListMessage::Publish {
+                        user_id: user_room_id.user_id.clone(),
+                        kind: kind.name(),
+                        reason: tr(DEFAULT_LOCALE, kind.message_id(), []),
+                    }
+                )?;
+            }
+            Ok(outcome)
+        }
+    }
+}
+
 impl Actor for Moderator {
     type Msg = ModeratorMessage;
-    type State = Client;
+    type State = ModeratorState;
     type Arguments = Client;
 
     async fn pre_start(
@@ -30,7 +432,33 @@ impl Actor for Moderator {
         _myself: ActorRef<Self::Msg>,
         args: Self::Arguments,
     ) -> Result<Self::State, ActorProcessingErr> {
-        Ok(args)
+        let mut ledger = None;
+        if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+            let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+            match Ledger::open(&config.ledger_path()) {
+                Ok(opened) => ledger = Some(opened),
+                Err(error) => error!(?error, "Unable to open the violation ledger"),
+            }
+        }
+        Ok(ModeratorState {
+            client: args,
+            missing_permissions: HashMap::new(),
+            ledger,
+        })
+    }
+
+    async fn post_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        for room in state.client.joined_rooms() {
+            ractor::cast!(
+                myself,
+                ModeratorMessage::CheckPermissions(room.room_id().into())
+            )?;
+        }
+        Ok(())
     }
 
     async fn handle(
@@ -40,16 +468,137 @@ impl Actor for Moderator {
         state: &mut Self::State,
     ) -> Result<(), ActorProcessingErr> {
         match message {
-            ModeratorMessage::Violation { user_room_id, kind } => {
-                if let Some(room) = state.get_room(&user_room_id.room_id) {
+            ModeratorMessage::Violation {
+                user_room_id,
+                monitor,
+                kind,
+                evidence,
+                sanction,
+            } => {
+                let outcome = sanction_user(state, &user_room_id, &kind, sanction).await;
+                if let Some(ledger) = &state.ledger {
+                    let record = ViolationRecord {
+                        timestamp_secs: now_secs(),
+                        user_id: user_room_id.user_id.to_string(),
+                        room_id: user_room_id.room_id.to_string(),
+                        monitor: monitor.to_string(),
+                        kind: kind.name().to_string(),
+                        evidence: evidence.iter().map(ToString::to_string).collect(),
+                        sanction: sanction.name().to_string(),
+                        outcome: outcome
+                            .as_ref()
+                            .map_or(Outcome::Failed, |outcome| *outcome)
+                            .as_str()
+                            .to_string(),
+                    };
+                    if let Err(error) = ledger.insert(&record) {
+                        error!(?error, "Unable to record violation");
+                    }
+                }
+                // The outcome is recorded, restarting the moderator would
+                // only report every missing permission again
+                if let Err(error) = outcome {
+                    error!(user = %user_room_id, ?error, "Unable to sanction user");
+                }
+            }
+            ModeratorMessage::RevokeInvite {
+                user_room_id,
+                inviter,
+            } => {
+                let reason = tr(
+                    &room_locale(&user_room_id).await?,
+                    "reason-invite-revoked",
+                    [],
+                );
+                if not_enforced(
+                    &state.client,
+                    &user_room_id,
+                    "log-report-revoke-invite",
+                    reason.clone(),
+                )
+                .await?
+                .is_some()
+                {
+                    return Ok(());
+                }
+                if let Some(room) = state.client.get_room(&user_room_id.room_id)
+                    && is_granted(&state.client, &room, Permission::Kick).await?
+                {
                     info!(
-                        "Kicking user {} from {} for {:?}",
-                        user_room_id.user_id, user_room_id.room_id, kind
+                        "Revoking invite of user {} to {} sent by {}",
+                        user_room_id.user_id, user_room_id.room_id, inviter
                     );
-                    room.kick_user(&user_room_id.user_id, Some(format!("{:?}", kind).as_str()))
-                        .await?;
+                    if let Err(error) = room
+                        .kick_user(&user_room_id.user_id, Some(reason.as_str()))
+                        .await
+                    {
+                        error!(user = %user_room_id, ?error, "Unable to revoke invite");
+                    }
                 }
             }
+            ModeratorMessage::Redact {
+                user_room_id,
+                event_id,
+                reason,
+            } => {
+                let reason = tr(&room_locale(&user_room_id).await?, reason, []);
+                if not_enforced(
+                    &state.client,
+                    &user_room_id,
+                    "log-report-redact",
+                    reason.clone(),
+                )
+                .await?
+                .is_some()
+                {
+                    return Ok(());
+                }
+                if let Some(room) = state.client.get_room(&user_room_id.room_id)
+                    && is_granted(&state.client, &room, Permission::Redact).await?
+                {
+                    info!(
+                        "Redacting event {} of user {} in {}",
+                        event_id, user_room_id.user_id, user_room_id.room_id
+                    );
+                    if let Err(error) = room.redact(&event_id, Some(reason.as_str()), None).await {
+                        error!(user = %user_room_id, ?error, "Unable to redact event");
+                    }
+                }
+            }
+            ModeratorMessage::Ban {
+                user_room_id,
+                reason,
+            } => {
+                if not_enforced(
+                    &state.client,
+                    &user_room_id,
+                    "log-report-ban",
+                    reason.clone(),
+                )
+                .await?
+                .is_some()
+                {
+                    return Ok(());
+                }
+                if let Some(room) = state.client.get_room(&user_room_id.room_id)
+                    && is_granted(&state.client, &room, Permission::Ban).await?
+                    && let Err(error) = room
+                        .ban_user(&user_room_id.user_id, Some(reason.as_str()))
+                        .await
+                {
+                    error!(user = %user_room_id, ?error, "Unable to ban user");
+                }
+            }
+            ModeratorMessage::CheckPermissions(room_id) => {
+                check_permissions(state, room_id).await?;
+            }
+            ModeratorMessage::History(filter, reply) => {
+                let records = match &state.ledger {
+                    Some(ledger) => ledger.query(&filter)?,
+                    None => vec![],
+                };
+                reply.send(records)?;
+            }
         };
         Ok(())
     }
@@ -0,0 +1,308 @@
+use rand::{
+    seq::{IndexedRandom, SliceRandom},
+    Rng,
+};
+use tracing::warn;
+
+use super::{
+    super::filter::escape_html,
+    image::{generate_code_image, CaptchaImage},
+};
+use crate::{
+    config::{CaptchaChoice, CaptchaQuestion, PuzzleKind},
+    i18n::tr,
+};
+
+/// Maximum number of choices offered for one question
+const MAX_CHOICES: usize = 10;
+
+const KEYCAPS: [&str; MAX_CHOICES] = ["1️⃣", "2️⃣", "3️⃣", "4️⃣", "5️⃣", "6️⃣", "7️⃣", "8️⃣", "9️⃣", "🔟"];
+
+/// Number of reactions offered for questions with a numbered answer
+const NUMBERED_CHOICES: usize = 5;
+
+const PUZZLE_WORDS: [&str; 12] = [
+    "apple", "banana", "garden", "matrix", "orange", "pencil", "rabbit", "silver", "tomato",
+    "window", "yellow", "zebra",
+];
+
+/// A question as presented to one user
+pub(super) struct Challenge {
+    pub(super) body: String,
+    pub(super) html_body: String,
+    /// Question without the list of choices, used as the poll question
+    pub(super) prompt: String,
+    /// Reactions the bot adds to the question so they can be clicked
+    pub(super) reactions: Vec<String>,
+    /// Labels of the choices, in the order of `reactions`
+    pub(super) labels: Vec<String>,
+    pub(super) answer: Answer,
+    /// Image the question refers to, sent with the question as caption
+    pub(super) image: Option<CaptchaImage>,
+}
+
+pub(super) enum Answer {
+    /// Keys of the reactions to the question that are correct. In a poll the
+    /// keys are the answer identifiers.
+    Reaction(Vec<String>),
+    /// Accepted replies, compared ignoring case and whitespace
+    Text(Vec<String>),
+}
+
+impl Answer {
+    pub(super) fn accepts_reaction(&self, key: &str) -> bool {
+        matches!(self, Answer::Reaction(accepted) if accepted.iter().any(|a| a == key))
+    }
+
+    pub(super) fn accepts_text(&self, text: &str) -> bool {
+        let text = normalize(text);
+        matches!(self, Answer::Text(accepted) if accepted.iter().any(|a| normalize(a) == text))
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl Challenge {
+    pub(super) fn new(question: &CaptchaQuestion, locale: &str) -> anyhow::Result<Challenge> {
+        Ok(match question {
+            CaptchaQuestion::Choice { body, answer } => Challenge {
+                body: body.clone(),
+                html_body: body.clone(),
+                prompt: body.clone(),
+                reactions: KEYCAPS[..NUMBERED_CHOICES]
+                    .iter()
+                    .map(|key| key.to_string())
+                    .collect(),
+                labels: KEYCAPS[..NUMBERED_CHOICES]
+                    .iter()
+                    .map(|key| key.to_string())
+                    .collect(),
+                answer: Answer::Reaction(vec![KEYCAPS[..NUMBERED_CHOICES]
+                    .get(usize::from(*answer).wrapping_sub(1))
+                    .unwrap_or(&"*️⃣")
+                    .to_string()]),
+                image: None,
+            },
+            CaptchaQuestion::Choices { body, choices } => shuffle_choices(body, choices),
+            CaptchaQuestion::Text { body, accept } => Challenge {
+                body: body.clone(),
+                html_body: body.clone(),
+                prompt: body.clone(),
+                reactions: vec![],
+                labels: vec![],
+                answer: Answer::Text(accept.clone()),
+                image: None,
+            },
+            CaptchaQuestion::Generated { generate } => generate_puzzle(*generate, locale)?,
+        })
+    }
+}
+
+/// Shuffles the choices and renders them as a list below the question. Choices
+/// without an emoji, or with the emoji of an earlier choice, get the first
+/// keycap no other choice uses.
+fn shuffle_choices(body: &str, choices: &[CaptchaChoice]) -> Challenge {
+    if choices.len() > MAX_CHOICES {
+        warn!(
+            count = choices.len(),
+            max = MAX_CHOICES,
+            "too many captcha choices, ignoring the rest"
+        );
+    }
+    let mut choices: Vec<_> = choices.iter().take(MAX_CHOICES).collect();
+    choices.shuffle(&mut rand::rng());
+
+    let mut keycaps = KEYCAPS
+        .iter()
+        .filter(|keycap| !choices.iter().any(|c| c.emoji.as_deref() == Some(**keycap)));
+    let mut lines = vec![];
+    let mut html_lines = vec![];
+    let mut reactions: Vec<String> = vec![];
+    let mut accepted = vec![];
+    for choice in &choices {
+        let key = match choice.emoji.as_deref() {
+            Some(emoji) if !reactions.iter().any(|key| key == emoji) => emoji,
+            _ => {
+                // At most MAX_CHOICES keys are taken, so a keycap is left
+                let Some(keycap) = keycaps.next() else {
+                    continue;
+                };
+                keycap
+            }
+        }
+        .to_string();
+        lines.push(format!("{key} {}", choice.label));
+        html_lines.push(format!(
+            "{} {}",
+            escape_html(&key),
+            escape_html(&choice.label)
+        ));
+        if choice.correct {
+            accepted.push(key.clone());
+        }
+        reactions.push(key);
+    }
+    if accepted.is_empty() {
+        warn!(body, "captcha question has no correct choice");
+    }
+    Challenge {
+        body: format!("{body}\n{}", lines.join("\n")),
+        html_body: format!("{body}<br>{}", html_lines.join("<br>")),
+        prompt: body.to_string(),
+        reactions,
+        labels: lines,
+        answer: Answer::Reaction(accepted),
+        image: None,
+    }
+}
+
+fn generate_puzzle(kind: PuzzleKind, locale: &str) -> anyhow::Result<Challenge> {
+    let mut rng = rand::rng();
+    let mut image = None;
+    let (body, answer) = match kind {
+        PuzzleKind::Arithmetic => {
+            let a: u32 = rng.random_range(2..=20);
+            let b: u32 = rng.random_range(2..=20);
+            let (id, x, y, answer) = match rng.random_range(0..3) {
+                0 => ("captcha-puzzle-add", a, b, a + b),
+                1 => ("captcha-puzzle-subtract", a.max(b), a.min(b), a.abs_diff(b)),
+                _ => ("captcha-puzzle-multiply", a, b, a * b),
+            };
+            let body = tr(locale, id, [("a", x.into()), ("b", y.into())]);
+            (body, answer.to_string())
+        }
+        PuzzleKind::Word => {
+            let word = *PUZZLE_WORDS.choose(&mut rng).unwrap_or(&"matrix");
+            if rng.random_bool(0.5) {
+                let body = tr(locale, "captcha-puzzle-reverse", [("word", word.into())]);
+                (body, word.chars().rev().collect())
+            } else {
+                let body = tr(locale, "captcha-puzzle-count", [("word", word.into())]);
+                (body, word.chars().count().to_string())
+            }
+        }
+        PuzzleKind::Image => {
+            let (code, code_image) = generate_code_image()?;
+            image = Some(code_image);
+            (tr(locale, "captcha-puzzle-image", []), code)
+        }
+    };
+    let body = format!("{body} {}", tr(locale, "captcha-reply-hint", []));
+    Ok(Challenge {
+        html_body: body.clone(),
+        prompt: body.clone(),
+        body,
+        reactions: vec![],
+        labels: vec![],
+        answer: Answer::Text(vec![answer]),
+        image,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_ignores_case_and_whitespace() {
+        assert_eq!(normalize(" Hello  World\n"), "helloworld");
+        assert_eq!(normalize("ÄBC"), "äbc");
+        assert_eq!(normalize(""), "");
+    }
+
+    #[test]
+    fn accepts_text() {
+        let answer = Answer::Text(vec!["Blue Sky".to_string(), "42".to_string()]);
+        assert!(answer.accepts_text("blue sky"));
+        assert!(answer.accepts_text("  BLUESKY "));
+        assert!(answer.accepts_text("42"));
+        assert!(!answer.accepts_text("43"));
+        assert!(!answer.accepts_text(""));
+    }
+
+    fn choice(label: &str, emoji: Option<&str>, correct: bool) -> CaptchaChoice {
+        CaptchaChoice {
+            label: label.to_string(),
+            emoji: emoji.map(str::to_string),
+            correct,
+        }
+    }
+
+    #[test]
+    fn shuffle_choices_keeps_every_choice() {
+        let choices = [
+            choice("Cat", None, true),
+            choice("Dog", None, false),
+            choice("Fish", Some("🐟"), false),
+        ];
+        let challenge = shuffle_choices("Pick the cat", &choices);
+        assert_eq!(challenge.reactions.len(), 3);
+        assert_eq!(challenge.labels.len(), 3);
+        assert!(challenge.reactions.iter().any(|key| key == "🐟"));
+        let cat = challenge
+            .labels
+            .iter()
+            .position(|label| label.ends_with("Cat"))
+            .unwrap();
+        assert!(challenge.answer.accepts_reaction(&challenge.reactions[cat]));
+        let dog = challenge
+            .labels
+            .iter()
+            .position(|label| label.ends_with("Dog"))
+            .unwrap();
+        assert!(!challenge.answer.accepts_reaction(&challenge.reactions[dog]));
+    }
+
+    #[test]
+    fn shuffle_choices_dedupes_keys() {
+        let choices = [
+            choice("A", Some("1️⃣"), true),
+            choice("B", None, false),
+            choice("C", Some("1️⃣"), false),
+            choice("D", None, false),
+        ];
+        for _ in 0..20 {
+            let challenge = shuffle_choices("Pick A", &choices);
+            assert_eq!(challenge.reactions.len(), 4);
+            for (i, key) in challenge.reactions.iter().enumerate() {
+                assert!(!challenge.reactions[i + 1..].contains(key));
+            }
+            let Answer::Reaction(accepted) = &challenge.answer else {
+                panic!("expected a reaction answer");
+            };
+            assert_eq!(accepted.len(), 1);
+        }
+    }
+
+    #[test]
+    fn shuffle_choices_caps_choices() {
+        let choices: Vec<_> = (0..MAX_CHOICES + 2)
+            .map(|i| choice(&i.to_string(), None, i == 0))
+            .collect();
+        let challenge = shuffle_choices("Pick 0", &choices);
+        assert_eq!(challenge.reactions.len(), MAX_CHOICES);
+    }
+
+    #[test]
+    fn shuffle_choices_escapes_html() {
+        let choices = [choice("<b>bold</b> & co", None, true)];
+        let challenge = shuffle_choices("Pick", &choices);
+        assert!(challenge
+            .html_body
+            .contains("&lt;b&gt;bold&lt;/b&gt; &amp; co"));
+        assert!(challenge.body.contains("<b>bold</b> & co"));
+    }
+
+    #[test]
+    fn reaction_answer_rejects_text() {
+        let answer = Answer::Reaction(vec!["1️⃣".to_string()]);
+        assert!(!answer.accepts_text("1️⃣"));
+        assert!(answer.accepts_reaction("1️⃣"));
+        assert!(!answer.accepts_reaction("2️⃣"));
+    }
+}
@@ -0,0 +1,266 @@
+use std::io::{self, Write};
+
+use flate2::{write::ZlibEncoder, Compression};
+use rand::{seq::IndexedRandom, Rng};
+
+/// Characters that are hard to confuse with each other once distorted
+const CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+const CODE_LENGTH: usize = 5;
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+/// 5x7 bitmap glyphs of `CODE_CHARS`, one row per byte with the leftmost
+/// pixel in bit 4
+const GLYPHS: [[u8; GLYPH_HEIGHT]; 32] = [
+    [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x1E], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+];
+
+const SCALE: f32 = 5.0;
+const CELL_WIDTH: usize = 36;
+const IMAGE_HEIGHT: usize = 64;
+const MARGIN: usize = 12;
+
+/// An encoded PNG image
+pub(super) struct CaptchaImage {
+    pub(super) data: Vec<u8>,
+    pub(super) width: u32,
+    pub(super) height: u32,
+}
+
+/// Where one character is drawn
+struct Placement {
+    glyph: &'static [u8; GLYPH_HEIGHT],
+    x: f32,
+    y: f32,
+    scale: f32,
+    shear: f32,
+}
+
+impl Placement {
+    fn covers(&self, x: f32, y: f32) -> bool {
+        let v = (y - self.y) / self.scale;
+        let u = (x - self.x - self.shear * (y - self.y)) / self.scale;
+        if u < 0.0 || v < 0.0 {
+            return false;
+        }
+        let (col, row) = (u as usize, v as usize);
+        col < GLYPH_WIDTH && row < GLYPH_HEIGHT && self.glyph[row] & (0x10 >> col) != 0
+    }
+}
+
+/// Generates a random code and renders it as a distorted image.
+pub(super) fn generate_code_image() -> io::Result<(String, CaptchaImage)> {
+    let mut rng = rand::rng();
+    let code: String = (0..CODE_LENGTH)
+        .map(|_| *CODE_CHARS.choose(&mut rng).unwrap_or(&b'A') as char)
+        .collect();
+    let image = render(&code)?;
+    Ok((code, image))
+}
+
+fn render(code: &str) -> io::Result<CaptchaImage> {
+    let mut rng = rand::rng();
+    let width = MARGIN * 2 + CELL_WIDTH * code.len();
+    let height = IMAGE_HEIGHT;
+    let placements: Vec<Placement> = code
+        .bytes()
+        .enumerate()
+        .filter_map(|(i, c)| {
+            let index = CODE_CHARS.iter().position(|&code_char| code_char == c)?;
+            let scale = SCALE * rng.random_range(0.85..1.15);
+            let glyph_height = scale * GLYPH_HEIGHT as f32;
+            Some(Placement {
+                glyph: &GLYPHS[index],
+                x: (MARGIN + CELL_WIDTH * i) as f32 + rng.random_range(0.0..6.0),
+                y: rng.random_range(4.0..(height as f32 - glyph_height - 4.0).max(5.0)),
+                scale,
+                shear: rng.random_range(-0.35..0.35),
+            })
+        })
+        .collect();
+
+    // Every pixel samples the glyphs through a wave so that the strokes bend
+    let (amplitude_x, amplitude_y) = (rng.random_range(2.0..4.0), rng.random_range(2.0..4.0));
+    let (period_x, period_y) = (rng.random_range(14.0..24.0), rng.random_range(24.0..40.0));
+    let (phase_x, phase_y) = (
+        rng.random_range(0.0..std::f32::consts::TAU),
+        rng.random_range(0.0..std::f32::consts::TAU),
+    );
+    let mut pixels = vec![0u8; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let sx = x as f32 + amplitude_x * (y as f32 / period_x + phase_x).sin();
+            let sy = y as f32 + amplitude_y * (x as f32 / period_y + phase_y).sin();
+            let ink = placements.iter().any(|p| p.covers(sx, sy));
+            pixels[y * width + x] = if ink {
+                rng.random_range(10..90)
+            } else {
+                rng.random_range(200..=255)
+            };
+        }
+    }
+
+    // Lines across the text and speckles make segmenting the characters harder
+    for _ in 0..3 {
+        let (x0, y0) = (0.0, rng.random_range(0.0..height as f32));
+        let (x1, y1) = (width as f32, rng.random_range(0.0..height as f32));
+        let steps = width * 2;
+        for step in 0..steps {
+            let t = step as f32 / steps as f32;
+            let x = (x0 + (x1 - x0) * t) as usize;
+            let y = (y0 + (y1 - y0) * t) as usize;
+            for dy in 0..2 {
+                if x < width && y + dy < height {
+                    pixels[(y + dy) * width + x] = rng.random_range(40..110);
+                }
+            }
+        }
+    }
+    for _ in 0..width * height / 40 {
+        let i = rng.random_range(0..pixels.len());
+        pixels[i] = rng.random_range(0..=255);
+    }
+
+    Ok(CaptchaImage {
+        data: encode_png(width as u32, height as u32, &pixels)?,
+        width: width as u32,
+        height: height as u32,
+    })
+}
+
+/// Encodes 8-bit grayscale pixels as a PNG image.
+fn encode_png(width: u32, height: u32, pixels: &[u8]) -> io::Result<Vec<u8>> {
+    fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        png.extend_from_slice(kind);
+        png.extend_from_slice(data);
+        let mut crc = crc32fast::Hasher::new();
+        crc.update(kind);
+        crc.update(data);
+        png.extend_from_slice(&crc.finalize().to_be_bytes());
+    }
+
+    let mut header = vec![];
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 8, grayscale, default compression, filter and no interlace
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    for row in pixels.chunks(width as usize) {
+        // Every scanline starts with its filter type, none here
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+    let data = encoder.finish()?;
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"IDAT", &data);
+    chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+
+    use super::*;
+
+    /// Splits the PNG into its chunks, checking the signature and the CRCs.
+    fn chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let mut chunks = vec![];
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+            let data = &rest[8..8 + len];
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(&kind);
+            hasher.update(data);
+            assert_eq!(hasher.finalize(), crc);
+            chunks.push((kind, data.to_vec()));
+            rest = &rest[12 + len..];
+        }
+        chunks
+    }
+
+    #[test]
+    fn encode_png_round_trips() {
+        let (width, height) = (3, 2);
+        let pixels = [0, 128, 255, 1, 2, 3];
+        let png = encode_png(width, height, &pixels).unwrap();
+        let chunks = chunks(&png);
+        let kinds: Vec<_> = chunks.iter().map(|(kind, _)| kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+
+        let header = &chunks[0].1;
+        assert_eq!(header.len(), 13);
+        assert_eq!(&header[..4], &width.to_be_bytes());
+        assert_eq!(&header[4..8], &height.to_be_bytes());
+        assert_eq!(&header[8..], &[8, 0, 0, 0, 0]);
+
+        let mut scanlines = vec![];
+        ZlibDecoder::new(chunks[1].1.as_slice())
+            .read_to_end(&mut scanlines)
+            .unwrap();
+        assert_eq!(scanlines, [0, 0, 128, 255, 0, 1, 2, 3]);
+        assert!(chunks[2].1.is_empty());
+    }
+
+    #[test]
+    fn generated_image_matches_dimensions() {
+        let (code, image) = generate_code_image().unwrap();
+        assert_eq!(code.len(), CODE_LENGTH);
+        assert!(code.bytes().all(|c| CODE_CHARS.contains(&c)));
+        let chunks = chunks(&image.data);
+        let header = &chunks[0].1;
+        assert_eq!(&header[..4], &image.width.to_be_bytes());
+        assert_eq!(&header[4..8], &image.height.to_be_bytes());
+        let mut scanlines = vec![];
+        ZlibDecoder::new(chunks[1].1.as_slice())
+            .read_to_end(&mut scanlines)
+            .unwrap();
+        assert_eq!(
+            scanlines.len(),
+            (image.width as usize + 1) * image.height as usize
+        );
+    }
+}
@@ -0,0 +1,478 @@
+use challenge::{Answer, Challenge};
+use matrix_sdk::{
+    attachment::{AttachmentConfig, AttachmentInfo, BaseImageInfo},
+    ruma::{
+        events::{
+            poll::unstable_start::{
+                NewUnstablePollStartEventContent, UnstablePollAnswer, UnstablePollAnswers,
+                UnstablePollStartContentBlock,
+            },
+            reaction::ReactionEventContent,
+            relation::Annotation,
+            room::message::{
+                sanitize::remove_plain_reply_fallback, FormattedBody, Relation,
+                RoomMessageEventContent,
+            },
+            Mentions,
+        },
+        OwnedEventId, OwnedRoomId,
+    },
+    Client,
+};
+use ractor::{concurrency::Duration, Actor, ActorProcessingErr, ActorRef};
+use rand::Rng;
+use tracing::{error, info, warn};
+
+use crate::{
+    actors::{
+        config_provider::ConfigProviderMessage,
+        moderator::{ModeratorMessage, ViolationKind},
+        reputation::{self, ReputationEvent},
+        spawner::SpawnerMessage,
+    },
+    config::{CaptchaPresentation, CaptchaQuestion, Quarantine, Sanction},
+    i18n::{tr, DEFAULT_LOCALE},
+    matrix::UserRoomId,
+};
+
+use super::{
+    filter::{escape_html, sanitize_display_name},
+    MonitorMessage,
+};
+
+mod challenge;
+mod image;
+
+pub(crate) struct CaptchaMonitor;
+
+/// What the captcha decides about the user
+#[derive(PartialEq)]
+pub(crate) enum CaptchaAdmission {
+    /// The user already joined the room and is removed if they fail
+    Join,
+    /// The user knocked on the room and is invited if they pass
+    Knock,
+}
+
+pub(crate) struct CaptchaInit {
+    pub(crate) user_room_id: UserRoomId,
+    pub(crate) client: Client,
+    /// Room where the question is posted, either the protected room or a DM
+    pub(crate) challenge_room_id: OwnedRoomId,
+    pub(crate) admission: CaptchaAdmission,
+}
+
+pub(crate) struct CaptchaState {
+    user_room_id: UserRoomId,
+    client: Client,
+    challenge_room_id: OwnedRoomId,
+    admission: CaptchaAdmission,
+    event_id: Option<OwnedEventId>,
+    answer: Option<Answer>,
+    display_name: String,
+    locale: String,
+    presentation: CaptchaPresentation,
+    questions: Vec<CaptchaQuestion>,
+    question_index: Option<usize>,
+    attempts_left: u32,
+    sanction: Sanction,
+    quarantine: Option<Quarantine>,
+}
+
+impl CaptchaState {
+    fn is_direct(&self) -> bool {
+        self.challenge_room_id != self.user_room_id.room_id
+    }
+}
+
+/// Picks a random question, different from the current one if possible.
+fn pick_question(count: usize, current: Option<usize>) -> Option<usize> {
+    match (count, current) {
+        (0, _) => None,
+        (1, _) | (_, None) => Some(rand::rng().random_range(0..count)),
+        (_, Some(current)) => Some((current + rand::rng().random_range(1..count)) % count),
+    }
+}
+
+/// Poll answers for the choices of the challenge, identified by the reaction
+/// keys of the choices. Returns `None` for questions without choices.
+fn poll_answers(challenge: &Challenge) -> Option<UnstablePollAnswers> {
+    let answers: Vec<_> = challenge
+        .reactions
+        .iter()
+        .zip(&challenge.labels)
+        .map(|(key, label)| UnstablePollAnswer::new(key, label))
+        .collect();
+    UnstablePollAnswers::try_from(answers).ok()
+}
+
+/// Posts a new question to the user, replacing the current one.
+async fn post_question(state: &mut CaptchaState) -> Result<(), ActorProcessingErr> {
+    let Some(index) = pick_question(state.questions.len(), state.question_index) else {
+        warn!(user = %state.user_room_id, locale = state.locale, "no captcha questions configured");
+        return Ok(());
+    };
+    let Some(room) = state.client.get_room(&state.challenge_room_id) else {
+        return Ok(());
+    };
+    if let Some(my_event_id) = state.event_id.take() {
+        room.redact(&my_event_id, None, None).await?;
+    }
+    let challenge = Challenge::new(&state.questions[index], &state.locale)?;
+    let display_name = state.display_name.as_str();
+    let poll_answers = match state.presentation {
+        CaptchaPresentation::Poll => poll_answers(&challenge),
+        CaptchaPresentation::Reactions => None,
+    };
+    if let Some(answers) = poll_answers {
+        // Clients without poll support only show the fallback text, which
+        // lists the choices.
+        let question = tr(
+            &state.locale,
+            "captcha-question",
+            [
+                ("name", display_name.into()),
+                ("question", challenge.prompt.into()),
+            ],
+        );
+        let fallback = format!("{question}\n{}", challenge.labels.join("\n"));
+        let content = NewUnstablePollStartEventContent::plain_text(
+            fallback,
+            UnstablePollStartContentBlock::new(question, answers),
+        );
+        let msg_response = room.send(content).await?;
+        state.event_id = Some(msg_response.event_id);
+        state.answer = Some(challenge.answer);
+        state.question_index = Some(index);
+        return Ok(());
+    }
+
+    let matrix_url = state.user_room_id.user_id.matrix_to_uri().to_string();
+    let body = tr(
+        &state.locale,
+        "captcha-question",
+        [
+            ("name", display_name.into()),
+            ("question", challenge.body.into()),
+        ],
+    );
+    let html_body = tr(
+        &state.locale,
+        "captcha-question",
+        [
+            (
+                "name",
+                format!("<a href='{matrix_url}'>{}</a>", escape_html(display_name)).into(),
+            ),
+            ("question", challenge.html_body.into()),
+        ],
+    );
+    let mentions = Mentions::with_user_ids([state.user_room_id.user_id.clone()]);
+    let msg_response = if let Some(image) = challenge.image {
+        let config = AttachmentConfig::new()
+            .caption(Some(body))
+            .formatted_caption(Some(FormattedBody::html(html_body)))
+            .mentions(Some(mentions))
+            .info(AttachmentInfo::Image(BaseImageInfo {
+                width: Some(image.width.into()),
+                height: Some(image.height.into()),
+                ..Default::default()
+            }));
+        room.send_attachment("captcha.png", &mime::IMAGE_PNG, image.data, config)
+            .await?
+    } else {
+        let content = RoomMessageEventContent::notice_html(body, html_body).add_mentions(mentions);
+        room.send(content).await?
+    };
+    for key in challenge.reactions {
+        let option = ReactionEventContent::new(Annotation::new(msg_response.event_id.clone(), key));
+        room.send(option).await?;
+    }
+    state.event_id = Some(msg_response.event_id);
+    state.answer = Some(challenge.answer);
+    state.question_index = Some(index);
+    Ok(())
+}
+
+fn sanction_user(state: &CaptchaState) -> Result<(), ActorProcessingErr> {
+    if let Some(moderator) = ActorRef::where_is("moderator".into()) {
+        ractor::cast!(
+            moderator,
+            ModeratorMessage::Violation {
+                user_room_id: state.user_room_id.clone(),
+                monitor: "captcha",
+                kind: ViolationKind::LikelyBot,
+                evidence: state.event_id.iter().cloned().collect(),
+                sanction: state.sanction,
+            }
+        )?;
+    } else {
+        error!("Unable to find moderator");
+    }
+    Ok(())
+}
+
+/// Applies the outcome of the user's answer. A wrong answer is followed by
+/// another question until the user runs out of attempts.
+async fn conclude(
+    myself: &ActorRef<MonitorMessage>,
+    state: &mut CaptchaState,
+    correct: bool,
+) -> Result<(), ActorProcessingErr> {
+    if !correct {
+        state.attempts_left = state.attempts_left.saturating_sub(1);
+        if state.attempts_left > 0 {
+            info!(
+                user = %state.user_room_id,
+                attempts_left = state.attempts_left,
+                "user provided wrong answer, asking another question"
+            );
+            return post_question(state).await;
+        }
+        info!(user = %state.user_room_id, "user provided wrong answer");
+        sanction_user(state)?;
+    } else {
+        reputation::record(&state.user_room_id.user_id, ReputationEvent::CaptchaPassed)?;
+        match state.admission {
+            CaptchaAdmission::Join => {
+                if let Some(monitor) =
+                    ActorRef::<MonitorMessage>::where_is(state.user_room_id.to_string())
+                {
+                    ractor::cast!(monitor, MonitorMessage::CaptchaPassed)?;
+                }
+            }
+            CaptchaAdmission::Knock => {
+                if let Some(room) = state.client.get_room(&state.user_room_id.room_id) {
+                    info!(user = %state.user_room_id, "inviting user who knocked");
+                    room.invite_user_by_id(&state.user_room_id.user_id).await?;
+                    if let Some(spawner) = ActorRef::where_is("spawner".into()) {
+                        ractor::cast!(
+                            spawner,
+                            SpawnerMessage::Admitted(state.user_room_id.clone())
+                        )?;
+                    }
+                }
+            }
+        }
+    }
+    if let Some(my_event_id) = &state.event_id
+        && let Some(room) = state.client.get_room(&state.challenge_room_id)
+    {
+        room.redact(my_event_id, None, None).await?;
+        state.event_id.take();
+        myself.stop(Some("answered".to_string()));
+    }
+    Ok(())
+}
+
+impl Actor for CaptchaMonitor {
+    type Msg = MonitorMessage;
+    type State = CaptchaState;
+    type Arguments = CaptchaInit;
+
+    async fn pre_start(
+        &self,
+        _myself: ractor::ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ractor::ActorProcessingErr> {
+        Ok(CaptchaState {
+            user_room_id: args.user_room_id,
+            client: args.client,
+            challenge_room_id: args.challenge_room_id,
+            admission: args.admission,
+            event_id: None,
+            answer: None,
+            display_name: String::new(),
+            locale: DEFAULT_LOCALE.to_string(),
+            presentation: CaptchaPresentation::default(),
+            questions: vec![],
+            question_index: None,
+            attempts_left: 0,
+            sanction: Sanction::default(),
+            quarantine: None,
+        })
+    }
+
+    async fn post_start(
+        &self,
+        myself: ractor::ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ractor::ActorProcessingErr> {
+        if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+            let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+
+            if let Some(captcha) =
+                config.monitor(state.user_room_id.room_id.as_str(), |m| &m.captcha)
+            {
+                let user = state
+                    .client
+                    .account()
+                    .fetch_user_profile_of(&state.user_room_id.user_id)
+                    .await?;
+                let localpart = state.user_room_id.user_id.localpart();
+                let keywords = config
+                    .monitor(state.user_room_id.room_id.as_str(), |m| &m.display_name)
+                    .map(|display_name| display_name.keywords)
+                    .unwrap_or_default();
+                state.display_name = user
+                    .displayname
+                    .map(|name| sanitize_display_name(&name, &keywords, localpart))
+                    .unwrap_or(localpart.to_string());
+                state.locale = config
+                    .locale(state.user_room_id.room_id.as_str())
+                    .to_string();
+                state.questions = captcha.questions_for(&state.locale).to_vec();
+                state.presentation = captcha.presentation;
+                state.attempts_left = captcha.attempts.max(1);
+                state.sanction = captcha.sanction;
+                state.quarantine = captcha.quarantine;
+                post_question(state).await?;
+                if state.event_id.is_some() {
+                    myself.send_after(Duration::from_secs(captcha.timeout_secs), || {
+                        MonitorMessage::Heartbeat
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        myself: ractor::ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ractor::ActorProcessingErr> {
+        match message {
+            MonitorMessage::Heartbeat => {
+                info!(user = %state.user_room_id, "user did not answer in time");
+                sanction_user(state)?;
+                if let Some(my_event_id) = &state.event_id
+                    && let Some(room) = state.client.get_room(&state.challenge_room_id)
+                {
+                    room.redact(my_event_id, None, None).await?;
+                    state.event_id.take();
+                    myself.stop(Some("moderated".to_string()));
+                }
+            }
+            MonitorMessage::ReactionMessage(msg) => {
+                info!(user = %state.user_room_id, "user answered");
+                // Reactions are routed to the monitor of their sender, so
+                // reactions of other users on the question never arrive here.
+                // Check the sender anyway so that they can never count as the
+                // user's answer.
+                if let Some(msg) = msg.as_original()
+                    && msg.sender == state.user_room_id.user_id
+                    && let Some(my_event_id) = &state.event_id
+                    && msg.content.relates_to.event_id == *my_event_id
+                    && let Some(answer) = &state.answer
+                {
+                    let correct = answer.accepts_reaction(&msg.content.relates_to.key);
+                    conclude(&myself, state, correct).await?;
+                }
+            }
+            MonitorMessage::PollResponse(msg) => {
+                if msg.sender == state.user_room_id.user_id
+                    && let Some(my_event_id) = &state.event_id
+                    && msg.content.relates_to.event_id == *my_event_id
+                    && let Some(answer) = &state.answer
+                {
+                    info!(user = %state.user_room_id, "user answered");
+                    let correct = msg
+                        .content
+                        .poll_response
+                        .answers
+                        .first()
+                        .is_some_and(|id| answer.accepts_reaction(id));
+                    conclude(&myself, state, correct).await?;
+                }
+            }
+            MonitorMessage::RoomMessage(msg) => {
+                if let Some(msg) = msg.as_original()
+                    && msg.sender == state.user_room_id.user_id
+                    && let Some(my_event_id) = &state.event_id
+                    && let Some(answer @ Answer::Text(_)) = &state.answer
+                {
+                    // In the room only replies to the question count as
+                    // answers, in a DM every message does.
+                    let is_reply = matches!(
+                        &msg.content.relates_to,
+                        Some(Relation::Reply { in_reply_to }) if in_reply_to.event_id == *my_event_id
+                    );
+                    if !is_reply && !state.is_direct() {
+                        return Ok(());
+                    }
+                    info!(user = %state.user_room_id, "user answered");
+                    let text = remove_plain_reply_fallback(msg.content.body());
+                    let correct = answer.accepts_text(text);
+                    // Answers in the room are hidden from the other members,
+                    // unless the quarantine already redacts them
+                    if !state.is_direct()
+                        && state.quarantine != Some(Quarantine::Redact)
+                        && let Some(moderator) = ActorRef::where_is("moderator".into())
+                    {
+                        ractor::cast!(
+                            moderator,
+                            ModeratorMessage::Redact {
+                                user_room_id: state.user_room_id.clone(),
+                                event_id: msg.event_id.clone(),
+                                reason: "reason-captcha-answer",
+                            }
+                        )?;
+                    }
+                    conclude(&myself, state, correct).await?;
+                }
+            }
+            _ => {}
+        };
+        Ok(())
+    }
+
+    async fn post_stop(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ractor::ActorProcessingErr> {
+        if let Some(my_event_id) = &state.event_id {
+            info!(user = %state.user_room_id, "user left without answer, redacting captcha");
+            if let Some(room) = state.client.get_room(&state.challenge_room_id) {
+                room.redact(my_event_id, None, None).await?;
+                myself.stop(Some("stopped".to_string()));
+            }
+        }
+        if state.is_direct()
+            && let Some(room) = state.client.get_room(&state.challenge_room_id)
+        {
+            room.leave().await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pick_question_without_questions() {
+        assert_eq!(pick_question(0, None), None);
+        assert_eq!(pick_question(0, Some(0)), None);
+    }
+
+    #[test]
+    fn pick_question_single() {
+        assert_eq!(pick_question(1, None), Some(0));
+        assert_eq!(pick_question(1, Some(0)), Some(0));
+    }
+
+    #[test]
+    fn pick_question_changes_question() {
+        for current in 0..3 {
+            for _ in 0..20 {
+                let index = pick_question(3, Some(current)).unwrap();
+                assert!(index < 3);
+                assert_ne!(index, current);
+            }
+        }
+    }
+}
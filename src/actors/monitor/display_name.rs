@@ -0,0 +1,122 @@
+use matrix_sdk::Client;
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use tracing::{error, info};
+
+use crate::{
+    actors::{
+        config_provider::ConfigProviderMessage,
+        moderator::{ModeratorMessage, ViolationKind},
+    },
+    config::{DisplayNameConfig, Sanction},
+    matrix::UserRoomId,
+};
+
+use super::{
+    filter::{contains_link, find_keyword},
+    MonitorMessage,
+};
+
+pub(super) struct DisplayNameMonitor;
+
+pub(super) struct DisplayNameInit {
+    pub(super) user_room_id: UserRoomId,
+    pub(super) client: Client,
+}
+
+pub(super) struct DisplayNameState {
+    user_room_id: UserRoomId,
+    client: Client,
+    config: DisplayNameConfig,
+}
+
+fn check_display_name(
+    state: &DisplayNameState,
+    display_name: &str,
+) -> Result<(), ActorProcessingErr> {
+    let kind = if state.config.block_links && contains_link(display_name) {
+        info!(user = %state.user_room_id, display_name, "user display name contains a link");
+        ViolationKind::Spam
+    } else if let Some(keyword) = find_keyword(display_name, &state.config.keywords) {
+        info!(
+            user = %state.user_room_id,
+            display_name,
+            keyword,
+            "user display name contains a blocked keyword"
+        );
+        ViolationKind::OffensiveDisplayName
+    } else {
+        return Ok(());
+    };
+    if let Some(moderator) = ActorRef::where_is("moderator".into()) {
+        ractor::cast!(
+            moderator,
+            ModeratorMessage::Violation {
+                user_room_id: state.user_room_id.clone(),
+                monitor: "display_name",
+                kind,
+                evidence: vec![],
+                sanction: Sanction::Kick,
+            }
+        )?;
+    } else {
+        error!("Unable to find moderator");
+    }
+    Ok(())
+}
+
+impl Actor for DisplayNameMonitor {
+    type Msg = MonitorMessage;
+    type State = DisplayNameState;
+    type Arguments = DisplayNameInit;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(DisplayNameState {
+            user_room_id: args.user_room_id,
+            client: args.client,
+            config: Default::default(),
+        })
+    }
+
+    async fn post_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+            let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+
+            if let Some(display_name) =
+                config.monitor(state.user_room_id.room_id.as_str(), |m| &m.display_name)
+            {
+                state.config = display_name;
+                if let Some(room) = state.client.get_room(&state.user_room_id.room_id)
+                    && let Some(member) = room.get_member(&state.user_room_id.user_id).await?
+                    && let Some(display_name) = member.display_name()
+                {
+                    check_display_name(state, display_name)?;
+                }
+            } else {
+                myself.stop(Some("disabled".into()));
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let MonitorMessage::MemberChange(ev) = message
+            && let Some(display_name) = &ev.content.displayname
+        {
+            check_display_name(state, display_name)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use unicode_security::skeleton;
+
+/// Maximum number of characters of a display name the bot repeats in its own
+/// messages.
+const MAX_DISPLAY_NAME_LEN: usize = 32;
+
+/// Folds text so that strings which look alike compare equal.
+pub(super) fn fold_text(text: &str) -> String {
+    skeleton(&text.to_lowercase())
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+        .collect()
+}
+
+pub(super) fn contains_link(text: &str) -> bool {
+    text.contains("https://") || text.contains("http://")
+}
+
+/// Splits the folded text into words, so that keywords are not found inside
+/// longer words.
+fn fold_words(text: &str) -> Vec<String> {
+    skeleton(&text.to_lowercase())
+        .flat_map(char::to_lowercase)
+        .collect::<String>()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Returns the first keyword found as whole words in the text after folding
+/// both sides.
+pub(super) fn find_keyword<'a>(text: &str, keywords: &'a [String]) -> Option<&'a str> {
+    let words = fold_words(text);
+    keywords
+        .iter()
+        .find(|keyword| {
+            let keyword = fold_words(keyword);
+            !keyword.is_empty() && words.windows(keyword.len()).any(|w| w == keyword)
+        })
+        .map(String::as_str)
+}
+
+/// Makes a display name safe to repeat in a notice. Names that carry links or
+/// blocked keywords are replaced by the fallback.
+pub(super) fn sanitize_display_name(name: &str, keywords: &[String], fallback: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_DISPLAY_NAME_LEN)
+        .collect();
+    let name = name.trim();
+    if name.is_empty() || contains_link(name) || find_keyword(name, keywords).is_some() {
+        fallback.to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+pub(super) fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keywords(keywords: &[&str]) -> Vec<String> {
+        keywords.iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn fold_text_ignores_case_whitespace_and_confusables() {
+        assert_eq!(fold_text("Robo T1"), fold_text("robot1"));
+        assert_eq!(fold_text("Pаypal"), fold_text("paypal"));
+        assert_ne!(fold_text("alice"), fold_text("bob"));
+    }
+
+    #[test]
+    fn find_keyword_matches_whole_words() {
+        let keywords = keywords(&["scam", "free crypto"]);
+        assert_eq!(find_keyword("Total SCAM here", &keywords), Some("scam"));
+        assert_eq!(find_keyword("scam!", &keywords), Some("scam"));
+        assert_eq!(
+            find_keyword("Get FREE   crypto", &keywords),
+            Some("free crypto")
+        );
+        assert_eq!(find_keyword("sсam", &keywords), Some("scam"));
+    }
+
+    #[test]
+    fn find_keyword_ignores_substrings() {
+        let keywords = keywords(&["scam", "free crypto", "cunt"]);
+        assert_eq!(find_keyword("Scunthorpe", &keywords), None);
+        assert_eq!(find_keyword("scamper", &keywords), None);
+        assert_eq!(find_keyword("freecrypto", &keywords), None);
+        assert_eq!(find_keyword("crypto free", &keywords), None);
+    }
+
+    #[test]
+    fn find_keyword_ignores_empty_keywords() {
+        assert_eq!(find_keyword("anything", &keywords(&["", " !"])), None);
+    }
+}
@@ -0,0 +1,183 @@
+use matrix_sdk::{ruma::MxcUri, Client, Room, RoomMemberships};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use tracing::{error, info};
+
+use crate::{
+    actors::{
+        config_provider::ConfigProviderMessage,
+        moderator::{ModeratorMessage, ViolationKind},
+    },
+    config::{ImpersonationConfig, Sanction},
+    matrix::UserRoomId,
+};
+
+use super::{filter::fold_text, MonitorMessage};
+
+pub(super) struct ImpersonationMonitor;
+
+pub(super) struct ImpersonationInit {
+    pub(super) user_room_id: UserRoomId,
+    pub(super) client: Client,
+}
+
+pub(super) struct ImpersonationState {
+    user_room_id: UserRoomId,
+    client: Client,
+    config: ImpersonationConfig,
+}
+
+/// Returns the protected name the display name is confusable with, if any.
+async fn impersonated_name(
+    room: &Room,
+    user_room_id: &UserRoomId,
+    display_name: &str,
+    config: &ImpersonationConfig,
+) -> anyhow::Result<Option<String>> {
+    let folded = fold_text(display_name);
+    if folded.is_empty() {
+        return Ok(None);
+    }
+    let members = room.members(RoomMemberships::JOIN).await?;
+    if members
+        .iter()
+        .any(|m| m.user_id() == user_room_id.user_id && m.power_level() >= config.power_level)
+    {
+        return Ok(None);
+    }
+    if let Some(name) = config
+        .protected_names
+        .iter()
+        .find(|name| fold_text(name) == folded)
+    {
+        return Ok(Some(name.clone()));
+    }
+    Ok(members
+        .iter()
+        .filter(|m| m.user_id() != user_room_id.user_id && m.power_level() >= config.power_level)
+        .find(|m| fold_text(m.name()) == folded)
+        .map(|m| m.user_id().to_string()))
+}
+
+/// Returns the protected member whose avatar the user copied, if any.
+async fn impersonated_avatar(
+    room: &Room,
+    user_room_id: &UserRoomId,
+    avatar_url: &MxcUri,
+    config: &ImpersonationConfig,
+) -> anyhow::Result<Option<String>> {
+    let members = room.members(RoomMemberships::JOIN).await?;
+    if members
+        .iter()
+        .any(|m| m.user_id() == user_room_id.user_id && m.power_level() >= config.power_level)
+    {
+        return Ok(None);
+    }
+    Ok(members
+        .iter()
+        .filter(|m| m.user_id() != user_room_id.user_id && m.power_level() >= config.power_level)
+        .find(|m| m.avatar_url() == Some(avatar_url))
+        .map(|m| m.user_id().to_string()))
+}
+
+async fn check_profile(
+    state: &ImpersonationState,
+    display_name: Option<&str>,
+    avatar_url: Option<&MxcUri>,
+) -> Result<(), ActorProcessingErr> {
+    let Some(room) = state.client.get_room(&state.user_room_id.room_id) else {
+        return Ok(());
+    };
+    let mut protected = None;
+    if let Some(display_name) = display_name {
+        protected =
+            impersonated_name(&room, &state.user_room_id, display_name, &state.config).await?;
+    }
+    if protected.is_none()
+        && let Some(avatar_url) = avatar_url
+    {
+        protected =
+            impersonated_avatar(&room, &state.user_room_id, avatar_url, &state.config).await?;
+    }
+    if let Some(protected) = protected {
+        info!(
+            user = %state.user_room_id,
+            display_name,
+            protected,
+            "user profile impersonates a protected member or name"
+        );
+        if let Some(moderator) = ActorRef::where_is("moderator".into()) {
+            ractor::cast!(
+                moderator,
+                ModeratorMessage::Violation {
+                    user_room_id: state.user_room_id.clone(),
+                    monitor: "impersonation",
+                    kind: ViolationKind::Impersonation,
+                    evidence: vec![],
+                    sanction: Sanction::Kick,
+                }
+            )?;
+        } else {
+            error!("Unable to find moderator");
+        }
+    }
+    Ok(())
+}
+
+impl Actor for ImpersonationMonitor {
+    type Msg = MonitorMessage;
+    type State = ImpersonationState;
+    type Arguments = ImpersonationInit;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(ImpersonationState {
+            user_room_id: args.user_room_id,
+            client: args.client,
+            config: Default::default(),
+        })
+    }
+
+    async fn post_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+            let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+
+            if let Some(impersonation) =
+                config.monitor(state.user_room_id.room_id.as_str(), |m| &m.impersonation)
+            {
+                state.config = impersonation;
+                if let Some(room) = state.client.get_room(&state.user_room_id.room_id)
+                    && let Some(member) = room.get_member(&state.user_room_id.user_id).await?
+                {
+                    check_profile(state, member.display_name(), member.avatar_url()).await?;
+                }
+            } else {
+                myself.stop(Some("disabled".into()));
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let MonitorMessage::MemberChange(ev) = message {
+            check_profile(
+                state,
+                ev.content.displayname.as_deref(),
+                ev.content.avatar_url.as_deref(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use tracing::{error, info};
+
+use crate::{
+    actors::{
+        config_provider::ConfigProviderMessage,
+        moderator::{ModeratorMessage, ViolationKind},
+    },
+    config::{InviteConfig, Sanction},
+    matrix::UserRoomId,
+};
+
+use super::MonitorMessage;
+
+pub(super) struct InviteMonitor;
+
+pub(super) struct InviteInit {
+    pub(super) user_room_id: UserRoomId,
+    pub(super) new_member: bool,
+}
+
+pub(super) struct InviteState {
+    user_room_id: UserRoomId,
+    config: InviteConfig,
+    joined_at: Option<Instant>,
+    invites: VecDeque<Instant>,
+}
+
+impl Actor for InviteMonitor {
+    type Msg = MonitorMessage;
+    type State = InviteState;
+    type Arguments = InviteInit;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(InviteState {
+            user_room_id: args.user_room_id,
+            config: Default::default(),
+            joined_at: args.new_member.then(Instant::now),
+            invites: VecDeque::new(),
+        })
+    }
+
+    async fn post_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+            let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+
+            if let Some(invite) = config.monitor(state.user_room_id.room_id.as_str(), |m| &m.invite)
+            {
+                state.config = invite;
+            } else {
+                myself.stop(Some("disabled".into()));
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let MonitorMessage::Invite(invitee) = message {
+            let window = Duration::from_secs(state.config.window_secs);
+            while state
+                .invites
+                .front()
+                .is_some_and(|invited_at| invited_at.elapsed() > window)
+            {
+                state.invites.pop_front();
+            }
+            state.invites.push_back(Instant::now());
+
+            let new_member = state.joined_at.is_some_and(|joined_at| {
+                joined_at.elapsed() < Duration::from_secs(state.config.new_member_timeout_secs)
+            });
+            let too_many = state.invites.len() > state.config.max_invites;
+            if !new_member && !too_many {
+                return Ok(());
+            }
+
+            let Some(moderator) = ActorRef::where_is("moderator".into()) else {
+                error!("Unable to find moderator");
+                return Ok(());
+            };
+            if new_member {
+                info!(user = %state.user_room_id, invitee = %invitee, "new member sent an invite");
+            }
+            ractor::cast!(
+                moderator,
+                ModeratorMessage::RevokeInvite {
+                    user_room_id: UserRoomId {
+                        user_id: invitee,
+                        room_id: state.user_room_id.room_id.clone(),
+                    },
+                    inviter: state.user_room_id.user_id.clone(),
+                }
+            )?;
+            if too_many {
+                info!(user = %state.user_room_id, "user exceeded invite limit");
+                ractor::cast!(
+                    moderator,
+                    ModeratorMessage::Violation {
+                        user_room_id: state.user_room_id.clone(),
+                        monitor: "invite",
+                        kind: ViolationKind::InviteSpam,
+                        evidence: vec![],
+                        sanction: Sanction::Kick,
+                    }
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
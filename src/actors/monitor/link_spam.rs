@@ -6,11 +6,11 @@ use crate::{
         config_provider::ConfigProviderMessage,
         moderator::{ModeratorMessage, ViolationKind},
     },
-    config::RoomConfig,
+    config::Sanction,
     matrix::UserRoomId,
 };
 
-use super::MonitorMessage;
+use super::{filter::contains_link, MonitorMessage};
 
 pub(super) struct LinkSpamMonitor;
 
@@ -31,19 +31,7 @@ impl Actor for LinkSpamMonitor {
         if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
             let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
 
-            if let Some(link_spam) = config
-                .rooms
-                .get(args.room_id.as_str())
-                .and_then(|room| match room {
-                    // FIXME: respect enabled
-                    RoomConfig::RoomEnabled(_) => None,
-                    RoomConfig::RoomDetail {
-                        enabled: _,
-                        monitors,
-                    } => monitors.link_spam.clone(),
-                })
-                .or(config.monitors.link_spam)
-            {
+            if let Some(link_spam) = config.monitor(args.room_id.as_str(), |m| &m.link_spam) {
                 myself.send_after(Duration::from_secs(link_spam.watch_timeout_secs), || {
                     MonitorMessage::Heartbeat
                 });
@@ -65,22 +53,23 @@ impl Actor for LinkSpamMonitor {
                 myself.stop(Some("waited long enough".into()));
             }
             MonitorMessage::RoomMessage(sync_message_like_event) => {
-                if let Some(evt) = sync_message_like_event.as_original() {
-                    if evt.content.body().contains("https://")
-                        || evt.content.body().contains("http://")
-                    {
-                        info!(user = %state.user_room_id, "user posted link right after join");
-                        if let Some(moderator) = ActorRef::where_is("moderator".into()) {
-                            ractor::cast!(
-                                moderator,
-                                ModeratorMessage::Violation {
-                                    user_room_id: state.user_room_id.clone(),
-                                    kind: ViolationKind::Spam,
-                                }
-                            )?;
-                        } else {
-                            tracing::error!("Unable to find moderator");
-                        }
+                if let Some(evt) = sync_message_like_event.as_original()
+                    && contains_link(evt.content.body())
+                {
+                    info!(user = %state.user_room_id, "user posted link right after join");
+                    if let Some(moderator) = ActorRef::where_is("moderator".into()) {
+                        ractor::cast!(
+                            moderator,
+                            ModeratorMessage::Violation {
+                                user_room_id: state.user_room_id.clone(),
+                                monitor: "link_spam",
+                                kind: ViolationKind::Spam,
+                                evidence: vec![evt.event_id.clone()],
+                                sanction: Sanction::Kick,
+                            }
+                        )?;
+                    } else {
+                        tracing::error!("Unable to find moderator");
                     }
                 }
             }
@@ -1,26 +1,58 @@
-use captcha::{CaptchaInit, CaptchaMonitor};
+pub(crate) use captcha::{CaptchaAdmission, CaptchaInit, CaptchaMonitor};
+use display_name::{DisplayNameInit, DisplayNameMonitor};
+use impersonation::{ImpersonationInit, ImpersonationMonitor};
+use invite::{InviteInit, InviteMonitor};
 use link_spam::LinkSpamMonitor;
 use matrix_sdk::{
-    ruma::events::{reaction::SyncReactionEvent, room::message::SyncRoomMessageEvent},
+    ruma::events::{
+        poll::unstable_response::OriginalSyncUnstablePollResponseEvent,
+        reaction::SyncReactionEvent,
+        room::{member::OriginalSyncRoomMemberEvent, message::SyncRoomMessageEvent},
+    },
+    ruma::OwnedUserId,
     Client,
 };
+use quarantine::{QuarantineInit, QuarantineMonitor};
 use ractor::{concurrency::Duration, pg, Actor, ActorProcessingErr, ActorRef};
-use ratelimit::RateLimitMonitor;
-use tracing::{error, info};
+use ratelimit::{RateLimitInit, RateLimitMonitor};
+use tracing::{error, info, warn};
+pub(crate) use welcome::copy_onboarded;
+use welcome::{WelcomeInit, WelcomeMonitor};
 
-use crate::matrix::UserRoomId;
+use crate::{
+    actors::{
+        config_provider::ConfigProviderMessage,
+        reputation::{self, ReputationEvent, Standing},
+    },
+    config::{CaptchaDelivery, MonitorConfig, Quarantine},
+    matrix::UserRoomId,
+};
 
 mod captcha;
+mod display_name;
+mod filter;
+mod impersonation;
+mod invite;
 mod link_spam;
+mod quarantine;
 mod ratelimit;
+mod welcome;
 
 const MONITOR_EXPIRE_TIMEOUT: u64 = 60 * 24;
 
 #[derive(Debug, Clone)]
 pub(crate) enum MonitorMessage {
     Heartbeat,
-    RoomMessage(SyncRoomMessageEvent),
-    ReactionMessage(SyncReactionEvent),
+    RoomMessage(Box<SyncRoomMessageEvent>),
+    ReactionMessage(Box<SyncReactionEvent>),
+    PollResponse(Box<OriginalSyncUnstablePollResponseEvent>),
+    MemberChange(Box<OriginalSyncRoomMemberEvent>),
+    /// The user invited another user to the room
+    Invite(OwnedUserId),
+    /// The user answered the captcha correctly
+    CaptchaPassed,
+    /// The user accepted the rules in the welcome notice
+    RulesAccepted,
 }
 
 pub(crate) struct MonitorState {
@@ -30,11 +62,26 @@ pub(crate) struct MonitorState {
 
 pub(crate) enum MonitorInit {
     Msg,
-    Join,
+    Join {
+        invited_by: Option<OwnedUserId>,
+        /// The user already passed the captcha when they knocked
+        admitted: bool,
+    },
 }
 
 pub(crate) struct Monitor;
 
+async fn monitor_config<T: Clone>(
+    user_room_id: &UserRoomId,
+    select: impl Fn(&MonitorConfig) -> &Option<T>,
+) -> Result<Option<T>, ActorProcessingErr> {
+    if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+        let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+        return Ok(config.monitor(user_room_id.room_id.as_str(), select));
+    }
+    Ok(None)
+}
+
 impl Actor for Monitor {
     type State = MonitorState;
     type Msg = MonitorMessage;
@@ -46,11 +93,23 @@ impl Actor for Monitor {
         args: Self::Arguments,
     ) -> Result<Self::State, ActorProcessingErr> {
         let (user_room_id, client, init) = args;
+        if let MonitorInit::Join {
+            invited_by: Some(inviter),
+            ..
+        } = &init
+        {
+            info!(user = %user_room_id, inviter = %inviter, "invited user joined");
+        }
+        reputation::record(&user_room_id.user_id, ReputationEvent::Seen)?;
+        let standing = reputation::standing(&user_room_id).await?;
         let mut monitors = vec![];
         let (ratelimit, _) = Actor::spawn_linked(
             None,
             RateLimitMonitor,
-            user_room_id.clone(),
+            RateLimitInit {
+                user_room_id: user_room_id.clone(),
+                standing,
+            },
             myself.get_cell(),
         )
         .await?;
@@ -61,20 +120,152 @@ impl Actor for Monitor {
             myself.get_cell(),
         )
         .await?;
+        let (impersonation, _) = Actor::spawn_linked(
+            None,
+            ImpersonationMonitor,
+            ImpersonationInit {
+                user_room_id: user_room_id.clone(),
+                client: client.clone(),
+            },
+            myself.get_cell(),
+        )
+        .await?;
+        let (display_name, _) = Actor::spawn_linked(
+            None,
+            DisplayNameMonitor,
+            DisplayNameInit {
+                user_room_id: user_room_id.clone(),
+                client: client.clone(),
+            },
+            myself.get_cell(),
+        )
+        .await?;
+        let (invite, _) = Actor::spawn_linked(
+            None,
+            InviteMonitor,
+            InviteInit {
+                user_room_id: user_room_id.clone(),
+                new_member: matches!(init, MonitorInit::Join { .. }),
+            },
+            myself.get_cell(),
+        )
+        .await?;
         monitors.push(ratelimit.get_cell());
         monitors.push(link_spam.get_cell());
-        if matches!(init, MonitorInit::Join) {
-            let (captcha, _) = Actor::spawn_linked(
+        monitors.push(impersonation.get_cell());
+        monitors.push(display_name.get_cell());
+        monitors.push(invite.get_cell());
+        let (mut captcha, welcome) = match init {
+            MonitorInit::Join { .. } if standing == Standing::Trusted => {
+                info!(user = %user_room_id, "trusted user joined, skipping captcha");
+                (None, monitor_config(&user_room_id, |m| &m.welcome).await?)
+            }
+            MonitorInit::Join { admitted: true, .. } => {
+                info!(user = %user_room_id, "admitted user joined, skipping captcha");
+                (None, monitor_config(&user_room_id, |m| &m.welcome).await?)
+            }
+            MonitorInit::Join { .. } => (
+                monitor_config(&user_room_id, |m| &m.captcha).await?,
+                monitor_config(&user_room_id, |m| &m.welcome).await?,
+            ),
+            MonitorInit::Msg => (None, None),
+        };
+        // The captcha falls back to the room when the DM cannot be created,
+        // for example when the user blocks invites
+        let mut dm = None;
+        if let Some(config) = &mut captcha
+            && config.delivery == CaptchaDelivery::Dm
+        {
+            match client.create_dm(&user_room_id.user_id).await {
+                Ok(room) => dm = Some(room),
+                Err(error) => {
+                    warn!(
+                        user = %user_room_id,
+                        ?error,
+                        "Unable to create captcha DM, posting the captcha in the room"
+                    );
+                    // Muted users could not answer in the room
+                    if config.quarantine == Some(Quarantine::PowerLevel) {
+                        config.quarantine = None;
+                    }
+                }
+            }
+        }
+        // Members who rejoin are not welcomed again
+        let welcome = match welcome {
+            Some(welcome) if !welcome::is_onboarded(&client, &user_room_id).await? => Some(welcome),
+            _ => None,
+        };
+        let until_rules_accepted = welcome
+            .as_ref()
+            .is_some_and(|welcome| welcome.rules.is_some());
+        if let Some(welcome) = welcome {
+            let (welcome, _) = Actor::spawn_linked(
+                None,
+                WelcomeMonitor,
+                WelcomeInit {
+                    user_room_id: user_room_id.clone(),
+                    client: client.clone(),
+                    config: welcome,
+                    captcha_pending: captcha.is_some(),
+                },
+                myself.get_cell(),
+            )
+            .await?;
+            monitors.push(welcome.get_cell());
+        }
+        if let Some(quarantine) = captcha.as_ref().and_then(|captcha| captcha.quarantine) {
+            let (quarantine, _) = Actor::spawn_linked(
                 None,
-                CaptchaMonitor,
-                CaptchaInit {
-                    user_room_id,
-                    client,
+                QuarantineMonitor,
+                QuarantineInit {
+                    user_room_id: user_room_id.clone(),
+                    client: client.clone(),
+                    quarantine,
+                    until_rules_accepted,
                 },
                 myself.get_cell(),
             )
             .await?;
-            monitors.push(captcha.get_cell());
+            monitors.push(quarantine.get_cell());
+        }
+        match (captcha, dm) {
+            (Some(_), None) => {
+                let (captcha, _) = Actor::spawn_linked(
+                    None,
+                    CaptchaMonitor,
+                    CaptchaInit {
+                        challenge_room_id: user_room_id.room_id.clone(),
+                        user_room_id,
+                        client,
+                        admission: CaptchaAdmission::Join,
+                    },
+                    myself.get_cell(),
+                )
+                .await?;
+                monitors.push(captcha.get_cell());
+            }
+            (Some(_), Some(dm)) => {
+                // The captcha is named after the DM so that the user's
+                // answers in the DM are routed directly to it.
+                let dm_user_room_id = UserRoomId {
+                    user_id: user_room_id.user_id.clone(),
+                    room_id: dm.room_id().into(),
+                };
+                Actor::spawn_linked(
+                    Some(dm_user_room_id.to_string()),
+                    CaptchaMonitor,
+                    CaptchaInit {
+                        user_room_id,
+                        client,
+                        challenge_room_id: dm_user_room_id.room_id,
+                        admission: CaptchaAdmission::Join,
+                    },
+                    myself.get_cell(),
+                )
+                .await?;
+            }
+            (None, _) => {}
         }
         pg::join(myself.get_id().to_string(), monitors);
         Ok(MonitorState {
@@ -115,13 +306,30 @@ impl Actor for Monitor {
                     myself.stop(Some("idled too long".into()));
                 }
             }
-            MonitorMessage::RoomMessage(_) => {
+            MonitorMessage::RoomMessage(ref msg) => {
+                reputation::record(msg.sender(), ReputationEvent::Message)?;
                 for mon in sub_monitors {
                     ractor::cast!(ActorRef::from(mon), message.clone())?;
                 }
                 state.last_msg_age = state.age;
             }
-            MonitorMessage::ReactionMessage(_) => {
+            MonitorMessage::ReactionMessage(_) | MonitorMessage::PollResponse(_) => {
+                for mon in sub_monitors {
+                    ractor::cast!(ActorRef::from(mon), message.clone())?;
+                }
+                state.last_msg_age = state.age;
+            }
+            MonitorMessage::MemberChange(_) => {
+                for mon in sub_monitors {
+                    ractor::cast!(ActorRef::from(mon), message.clone())?;
+                }
+            }
+            MonitorMessage::CaptchaPassed | MonitorMessage::RulesAccepted => {
+                for mon in sub_monitors {
+                    ractor::cast!(ActorRef::from(mon), message.clone())?;
+                }
+            }
+            MonitorMessage::Invite(_) => {
                 for mon in sub_monitors {
                     ractor::cast!(ActorRef::from(mon), message.clone())?;
                 }
@@ -0,0 +1,215 @@
+use matrix_sdk::{
+    ruma::{
+        events::room::{member::MembershipState, power_levels::RoomPowerLevelsEventContent},
+        Int, UserId,
+    },
+    Client, Room,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use tracing::{error, info};
+
+use crate::{
+    actors::{
+        config_provider::ConfigProviderMessage,
+        moderator::{not_enforced, ModeratorMessage},
+    },
+    config::Quarantine,
+    i18n::{tr, DEFAULT_LOCALE},
+    matrix::UserRoomId,
+};
+
+use super::MonitorMessage;
+
+/// Restricts a new member until they pass the captcha and, if required,
+/// accept the rules.
+pub(super) struct QuarantineMonitor;
+
+pub(super) struct QuarantineInit {
+    pub(super) user_room_id: UserRoomId,
+    pub(super) client: Client,
+    pub(super) quarantine: Quarantine,
+    /// Keep the quarantine after the captcha until the user accepts the rules
+    pub(super) until_rules_accepted: bool,
+}
+
+pub(super) struct QuarantineState {
+    user_room_id: UserRoomId,
+    client: Client,
+    quarantine: Quarantine,
+    until_rules_accepted: bool,
+    /// Power level explicitly assigned to the user before the quarantine
+    previous_power_level: Option<Int>,
+    lowered: bool,
+}
+
+/// Assigns the power level to the user, or removes their entry to fall back
+/// to the room default.
+async fn set_power_level(
+    room: &Room,
+    user_id: &UserId,
+    power_level: Option<Int>,
+) -> anyhow::Result<()> {
+    let mut content = RoomPowerLevelsEventContent::from(room.power_levels().await?);
+    match power_level {
+        Some(power_level) => content.users.insert(user_id.to_owned(), power_level),
+        None => content.users.remove(user_id),
+    };
+    room.send_state_event(content).await?;
+    Ok(())
+}
+
+/// Whether the user left the room by themselves, rather than being kicked or
+/// banned.
+async fn user_left(state: &QuarantineState) -> anyhow::Result<bool> {
+    let Some(room) = state.client.get_room(&state.user_room_id.room_id) else {
+        return Ok(false);
+    };
+    Ok(room
+        .get_member(&state.user_room_id.user_id)
+        .await?
+        .is_some_and(|member| {
+            *member.membership() == MembershipState::Leave
+                && member.event().sender() == state.user_room_id.user_id
+        }))
+}
+
+async fn lift(state: &mut QuarantineState) -> Result<(), ActorProcessingErr> {
+    if state.lowered
+        && let Some(room) = state.client.get_room(&state.user_room_id.room_id)
+    {
+        info!(user = %state.user_room_id, "restoring power level of quarantined user");
+        set_power_level(
+            &room,
+            &state.user_room_id.user_id,
+            state.previous_power_level,
+        )
+        .await?;
+        state.lowered = false;
+    }
+    Ok(())
+}
+
+impl Actor for QuarantineMonitor {
+    type Msg = MonitorMessage;
+    type State = QuarantineState;
+    type Arguments = QuarantineInit;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(QuarantineState {
+            user_room_id: args.user_room_id,
+            client: args.client,
+            quarantine: args.quarantine,
+            until_rules_accepted: args.until_rules_accepted,
+            previous_power_level: None,
+            lowered: false,
+        })
+    }
+
+    async fn post_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        let mut locale = DEFAULT_LOCALE.to_string();
+        if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+            let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+            let room_id = state.user_room_id.room_id.as_str();
+            locale = config.locale(room_id).to_string();
+            // Without questions the captcha can never be passed
+            if config
+                .monitor(room_id, |m| &m.captcha)
+                .is_none_or(|captcha| captcha.questions_for(&locale).is_empty())
+            {
+                info!(user = %state.user_room_id, "no captcha questions, skipping quarantine");
+                myself.stop(Some("no captcha questions".into()));
+                return Ok(());
+            }
+        }
+        if state.quarantine == Quarantine::PowerLevel
+            && let Some(room) = state.client.get_room(&state.user_room_id.room_id)
+        {
+            let reason = tr(&locale, "reason-quarantine", []);
+            if not_enforced(
+                &state.client,
+                &state.user_room_id,
+                "log-report-quarantine",
+                reason,
+            )
+            .await?
+            .is_some()
+            {
+                return Ok(());
+            }
+            let power_levels = room.power_levels().await?;
+            let muted = power_levels.events_default - Int::from(1);
+            // A user kicked during an earlier quarantine still holds the
+            // lowered level, which must not be restored after this one
+            state.previous_power_level = power_levels
+                .users
+                .get(&state.user_room_id.user_id)
+                .copied()
+                .filter(|level| *level != muted);
+            info!(user = %state.user_room_id, "lowering power level of new member");
+            set_power_level(&room, &state.user_room_id.user_id, Some(muted)).await?;
+            state.lowered = true;
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            MonitorMessage::RoomMessage(msg) if state.quarantine == Quarantine::Redact => {
+                info!(user = %state.user_room_id, "redacting message sent before captcha");
+                if let Some(moderator) = ActorRef::where_is("moderator".into()) {
+                    ractor::cast!(
+                        moderator,
+                        ModeratorMessage::Redact {
+                            user_room_id: state.user_room_id.clone(),
+                            event_id: msg.event_id().to_owned(),
+                            reason: "reason-quarantine",
+                        }
+                    )?;
+                } else {
+                    error!("Unable to find moderator");
+                }
+            }
+            MonitorMessage::CaptchaPassed if !state.until_rules_accepted => {
+                lift(state).await?;
+                myself.stop(Some("captcha passed".into()));
+            }
+            // Muted users cannot react to accept the rules, so the mute is
+            // lifted and their messages are redacted until they accept
+            MonitorMessage::CaptchaPassed if state.quarantine == Quarantine::PowerLevel => {
+                lift(state).await?;
+                state.quarantine = Quarantine::Redact;
+            }
+            MonitorMessage::RulesAccepted => {
+                lift(state).await?;
+                myself.stop(Some("rules accepted".into()));
+            }
+            _ => {}
+        };
+        Ok(())
+    }
+
+    async fn post_stop(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        // Kicked or banned users stay restricted if they come back
+        if state.lowered && user_left(state).await? {
+            lift(state).await?;
+        }
+        Ok(())
+    }
+}
@@ -5,8 +5,9 @@ use crate::{
     actors::{
         config_provider::ConfigProviderMessage,
         moderator::{ModeratorMessage, ViolationKind},
+        reputation::Standing,
     },
-    config::{RateLimitConfig, RoomConfig},
+    config::{RateLimitConfig, Sanction},
     matrix::UserRoomId,
 };
 
@@ -14,8 +15,14 @@ use super::MonitorMessage;
 
 pub(super) struct RateLimitMonitor;
 
+pub(super) struct RateLimitInit {
+    pub(super) user_room_id: UserRoomId,
+    pub(super) standing: Standing,
+}
+
 pub(super) struct RateLimitState {
     user_room_id: UserRoomId,
+    standing: Standing,
     bucket: Bucket,
     config: RateLimitConfig,
 }
@@ -23,7 +30,7 @@ pub(super) struct RateLimitState {
 impl Actor for RateLimitMonitor {
     type State = RateLimitState;
     type Msg = MonitorMessage;
-    type Arguments = UserRoomId;
+    type Arguments = RateLimitInit;
 
     async fn pre_start(
         &self,
@@ -31,7 +38,8 @@ impl Actor for RateLimitMonitor {
         args: Self::Arguments,
     ) -> Result<Self::State, ActorProcessingErr> {
         Ok(RateLimitState {
-            user_room_id: args,
+            user_room_id: args.user_room_id,
+            standing: args.standing,
             bucket: Bucket::new(),
             config: Default::default(),
         })
@@ -45,23 +53,17 @@ impl Actor for RateLimitMonitor {
         if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
             let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
 
-            if let Some(rate_limit) = config
-                .rooms
-                .get(state.user_room_id.room_id.as_str())
-                .and_then(|room| match room {
-                    // FIXME: respect enabled
-                    RoomConfig::RoomEnabled(_) => None,
-                    RoomConfig::RoomDetail {
-                        enabled: _,
-                        monitors,
-                    } => monitors.rate_limit.clone(),
-                })
-                .or(config.monitors.rate_limit)
+            if let Some(rate_limit) =
+                config.monitor(state.user_room_id.room_id.as_str(), |m| &m.rate_limit)
             {
                 state.bucket.token_current = rate_limit.token_new;
                 state.bucket.token_max = rate_limit.token_new_max;
                 state.bucket.fill_rate = rate_limit.fill_rate;
                 state.bucket.fill_freq = Duration::from_secs(rate_limit.fill_freq_secs);
+                if state.standing == Standing::Trusted {
+                    state.bucket.token_current = rate_limit.token_join;
+                    state.bucket.token_max = rate_limit.token_join_max;
+                }
                 state.config = rate_limit;
                 myself.send_after(
                     Duration::from_secs(state.config.token_new_timeout_secs),
@@ -83,12 +85,22 @@ impl Actor for RateLimitMonitor {
         match message {
             MonitorMessage::Heartbeat => {
                 state.bucket.fill(state.bucket.fill_rate);
-                if state.bucket.token_max == state.config.token_new_max {
+                if state.standing != Standing::Untrusted
+                    && state.bucket.token_max == state.config.token_new_max
+                {
                     state.bucket.token_max = state.config.token_join_max;
                 }
                 myself.send_after(state.bucket.fill_freq, || MonitorMessage::Heartbeat);
             }
-            MonitorMessage::RoomMessage(_) | MonitorMessage::ReactionMessage(_) => {
+            MonitorMessage::RoomMessage(_)
+            | MonitorMessage::ReactionMessage(_)
+            | MonitorMessage::PollResponse(_) => {
+                let event_id = match &message {
+                    MonitorMessage::RoomMessage(ev) => Some(ev.event_id().to_owned()),
+                    MonitorMessage::ReactionMessage(ev) => Some(ev.event_id().to_owned()),
+                    MonitorMessage::PollResponse(ev) => Some(ev.event_id.clone()),
+                    _ => None,
+                };
                 if !state.bucket.consume(1.0) {
                     info!(user = %state.user_room_id, "user exceeded rate limit");
                     if let Some(moderator) = ActorRef::where_is("moderator".into()) {
@@ -96,7 +108,10 @@ impl Actor for RateLimitMonitor {
                             moderator,
                             ModeratorMessage::Violation {
                                 user_room_id: state.user_room_id.clone(),
+                                monitor: "ratelimit",
                                 kind: ViolationKind::Spam,
+                                evidence: event_id.into_iter().collect(),
+                                sanction: Sanction::Kick,
                             }
                         )?;
                     } else {
@@ -104,6 +119,10 @@ impl Actor for RateLimitMonitor {
                     }
                 }
             }
+            MonitorMessage::MemberChange(_)
+            | MonitorMessage::Invite(_)
+            | MonitorMessage::CaptchaPassed
+            | MonitorMessage::RulesAccepted => {}
         };
         Ok(())
     }
@@ -0,0 +1,234 @@
+use matrix_sdk::{
+    ruma::{
+        events::{
+            reaction::ReactionEventContent, relation::Annotation,
+            room::message::RoomMessageEventContent, Mentions,
+        },
+        OwnedEventId,
+    },
+    Client,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use tracing::info;
+
+use crate::{
+    actors::config_provider::ConfigProviderMessage,
+    config::WelcomeConfig,
+    i18n::{tr, DEFAULT_LOCALE},
+    matrix::UserRoomId,
+};
+
+use super::{
+    filter::{escape_html, sanitize_display_name},
+    MonitorMessage,
+};
+
+/// Reaction used to accept the rules
+const ACCEPT_KEY: &str = "✅";
+
+/// Greets a new member once they pass the captcha and waits for them to
+/// accept the rules.
+pub(super) struct WelcomeMonitor;
+
+pub(super) struct WelcomeInit {
+    pub(super) user_room_id: UserRoomId,
+    pub(super) client: Client,
+    pub(super) config: WelcomeConfig,
+    /// Wait for the captcha to be passed before welcoming the user
+    pub(super) captcha_pending: bool,
+}
+
+pub(super) struct WelcomeState {
+    user_room_id: UserRoomId,
+    client: Client,
+    config: WelcomeConfig,
+    captcha_pending: bool,
+    event_id: Option<OwnedEventId>,
+}
+
+fn onboarded_key(user_room_id: &UserRoomId) -> Vec<u8> {
+    format!(
+        "t1bot.onboarded.{}.{}",
+        user_room_id.room_id, user_room_id.user_id
+    )
+    .into_bytes()
+}
+
+/// Returns whether the user was already welcomed to the room and accepted
+/// its rules.
+pub(super) async fn is_onboarded(
+    client: &Client,
+    user_room_id: &UserRoomId,
+) -> Result<bool, ActorProcessingErr> {
+    let value = client
+        .state_store()
+        .get_custom_value(&onboarded_key(user_room_id))
+        .await?;
+    Ok(value.is_some())
+}
+
+/// Carries the onboarded flag of the user over to another room, used when a
+/// room is upgraded.
+pub(crate) async fn copy_onboarded(
+    client: &Client,
+    from: &UserRoomId,
+    to: &UserRoomId,
+) -> Result<(), ActorProcessingErr> {
+    if is_onboarded(client, from).await? {
+        client
+            .state_store()
+            .set_custom_value(&onboarded_key(to), vec![1])
+            .await?;
+    }
+    Ok(())
+}
+
+async fn set_onboarded(state: &WelcomeState) -> Result<(), ActorProcessingErr> {
+    state
+        .client
+        .state_store()
+        .set_custom_value(&onboarded_key(&state.user_room_id), vec![1])
+        .await?;
+    Ok(())
+}
+
+async fn post_welcome(
+    myself: &ActorRef<MonitorMessage>,
+    state: &mut WelcomeState,
+) -> Result<(), ActorProcessingErr> {
+    let Some(room) = state.client.get_room(&state.user_room_id.room_id) else {
+        return Ok(());
+    };
+    let mut locale = DEFAULT_LOCALE.to_string();
+    let mut keywords = vec![];
+    if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+        let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+        let room_id = state.user_room_id.room_id.as_str();
+        locale = config.locale(room_id).to_string();
+        keywords = config
+            .monitor(room_id, |m| &m.display_name)
+            .map(|display_name| display_name.keywords)
+            .unwrap_or_default();
+    }
+    let localpart = state.user_room_id.user_id.localpart();
+    let display_name = state
+        .client
+        .account()
+        .fetch_user_profile_of(&state.user_room_id.user_id)
+        .await?
+        .displayname
+        .map(|name| sanitize_display_name(&name, &keywords, localpart))
+        .unwrap_or(localpart.to_string());
+    let matrix_url = state.user_room_id.user_id.matrix_to_uri().to_string();
+
+    let mut body = state.config.message.replace("{name}", &display_name);
+    let mut html_body = escape_html(&state.config.message).replace(
+        "{name}",
+        &format!("<a href='{matrix_url}'>{}</a>", escape_html(&display_name)),
+    );
+    if let Some(rules) = &state.config.rules {
+        let hint = tr(&locale, "welcome-rules-hint", []);
+        body = format!("{body}\n\n{rules}\n\n{hint}");
+        html_body = format!(
+            "{html_body}<br><br>{}<br><br>{}",
+            escape_html(rules).replace('\n', "<br>"),
+            escape_html(&hint)
+        );
+    }
+    let content = RoomMessageEventContent::notice_html(body, html_body).add_mentions(
+        Mentions::with_user_ids([state.user_room_id.user_id.clone()]),
+    );
+    info!(user = %state.user_room_id, "welcoming new member");
+    let msg_response = room.send(content).await?;
+    if state.config.rules.is_some() {
+        let accept = ReactionEventContent::new(Annotation::new(
+            msg_response.event_id.clone(),
+            ACCEPT_KEY.to_string(),
+        ));
+        room.send(accept).await?;
+        state.event_id = Some(msg_response.event_id);
+    } else {
+        set_onboarded(state).await?;
+        myself.stop(Some("welcomed".into()));
+    }
+    Ok(())
+}
+
+impl Actor for WelcomeMonitor {
+    type Msg = MonitorMessage;
+    type State = WelcomeState;
+    type Arguments = WelcomeInit;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(WelcomeState {
+            user_room_id: args.user_room_id,
+            client: args.client,
+            config: args.config,
+            captcha_pending: args.captcha_pending,
+            event_id: None,
+        })
+    }
+
+    async fn post_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if !state.captcha_pending {
+            post_welcome(&myself, state).await?;
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            MonitorMessage::CaptchaPassed if state.captcha_pending => {
+                state.captcha_pending = false;
+                post_welcome(&myself, state).await?;
+            }
+            MonitorMessage::ReactionMessage(msg) => {
+                if let Some(msg) = msg.as_original()
+                    && msg.sender == state.user_room_id.user_id
+                    && let Some(my_event_id) = &state.event_id
+                    && msg.content.relates_to.event_id == *my_event_id
+                    && msg.content.relates_to.key == ACCEPT_KEY
+                {
+                    info!(user = %state.user_room_id, "user accepted the rules");
+                    state.event_id.take();
+                    set_onboarded(state).await?;
+                    if let Some(monitor) =
+                        ActorRef::<MonitorMessage>::where_is(state.user_room_id.to_string())
+                    {
+                        ractor::cast!(monitor, MonitorMessage::RulesAccepted)?;
+                    }
+                    myself.stop(Some("rules accepted".into()));
+                }
+            }
+            _ => {}
+        };
+        Ok(())
+    }
+
+    async fn post_stop(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let Some(my_event_id) = &state.event_id
+            && let Some(room) = state.client.get_room(&state.user_room_id.room_id)
+        {
+            info!(user = %state.user_room_id, "user left without accepting the rules");
+            room.redact(my_event_id, None, None).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,380 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use matrix_sdk::{
+    deserialized_responses::RawAnySyncOrStrippedState,
+    ruma::{
+        events::{
+            policy::rule::{
+                user::PolicyRuleUserEventContent, PolicyRuleEventContent, Recommendation,
+            },
+            StateEventType,
+        },
+        OwnedRoomId, OwnedUserId, RoomId, UserId,
+    },
+    Client, Room, RoomMemberships,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef, RpcReplyPort};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{PolicyListConfig, Sanction},
+    matrix::UserRoomId,
+};
+
+use super::{
+    config_provider::ConfigProviderMessage,
+    moderator::{ModeratorMessage, ViolationKind},
+};
+
+/// Keeps the ban recommendations of the subscribed policy rooms and bans the
+/// members they match.
+pub(crate) struct PolicyList;
+
+pub(crate) enum PolicyListMessage {
+    /// Reloads the rules of a policy room after they changed
+    Reload(OwnedRoomId),
+    /// Reloads the changed policy rooms and bans the members matching them,
+    /// once per burst of changes
+    Enforce,
+    /// Returns the reason of the first rule matching the user
+    Match(OwnedUserId, RpcReplyPort<Option<String>>),
+    /// Publishes a ban issued by the bot to the policy room of the bot, if
+    /// bans of the violation kind are published
+    Publish {
+        user_id: OwnedUserId,
+        kind: &'static str,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PolicyKind {
+    User,
+    Server,
+    Room,
+}
+
+#[derive(Debug)]
+struct PolicyRule {
+    kind: PolicyKind,
+    /// Glob of the banned entity
+    entity: String,
+    reason: String,
+}
+
+pub(crate) struct PolicyListState {
+    client: Client,
+    config: PolicyListConfig,
+    /// Rules of every subscribed policy room
+    rules: HashMap<OwnedRoomId, Vec<PolicyRule>>,
+    /// Policy rooms that changed since the last enforcement
+    changed: HashSet<OwnedRoomId>,
+}
+
+/// Delay between the first change of a policy room and the enforcement, so
+/// that a burst of new rules causes a single scan of the members
+const ENFORCE_DELAY: Duration = Duration::from_secs(10);
+
+/// Matches the text against a glob where `*` matches any number of
+/// characters and `?` matches exactly one.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it was tried at
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            backtrack = Some((p, t));
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+impl PolicyListState {
+    fn rules(&self) -> impl Iterator<Item = &PolicyRule> {
+        self.rules.values().flatten()
+    }
+
+    fn match_user(&self, user_id: &UserId) -> Option<&PolicyRule> {
+        self.rules().find(|rule| match rule.kind {
+            PolicyKind::User => glob_match(&rule.entity, user_id.as_str()),
+            PolicyKind::Server => glob_match(&rule.entity, user_id.server_name().as_str()),
+            PolicyKind::Room => false,
+        })
+    }
+
+    fn match_room(&self, room_id: &RoomId) -> Option<&PolicyRule> {
+        self.rules().find(|rule| {
+            rule.kind == PolicyKind::Room && glob_match(&rule.entity, room_id.as_str())
+        })
+    }
+}
+
+/// Reads the ban rules of one kind from the state of the policy room. Rules
+/// that were removed by replacing their content are skipped.
+async fn read_rules(
+    room: &Room,
+    event_type: StateEventType,
+    kind: PolicyKind,
+) -> Result<Vec<PolicyRule>, ActorProcessingErr> {
+    let mut rules = vec![];
+    for raw in room.get_state_events(event_type).await? {
+        // All policy rule kinds share the same content
+        if let RawAnySyncOrStrippedState::Sync(raw) = raw
+            && let Ok(Some(rule)) = raw.get_field::<PolicyRuleEventContent>("content")
+            && rule.recommendation == Recommendation::Ban
+        {
+            rules.push(PolicyRule {
+                kind,
+                entity: rule.entity,
+                reason: rule.reason,
+            });
+        }
+    }
+    Ok(rules)
+}
+
+async fn reload(state: &mut PolicyListState, room_id: &RoomId) -> Result<(), ActorProcessingErr> {
+    let Some(room) = state.client.get_room(room_id) else {
+        return Ok(());
+    };
+    let mut rules = vec![];
+    for (event_type, kind) in [
+        (StateEventType::PolicyRuleUser, PolicyKind::User),
+        (StateEventType::PolicyRuleServer, PolicyKind::Server),
+        (StateEventType::PolicyRuleRoom, PolicyKind::Room),
+    ] {
+        rules.extend(read_rules(&room, event_type, kind).await?);
+    }
+    info!(room = %room_id, count = rules.len(), "loaded policy list");
+    state.rules.insert(room_id.to_owned(), rules);
+    Ok(())
+}
+
+/// Bans the members of the protected rooms who match the rules.
+async fn enforce(state: &PolicyListState) -> Result<(), ActorProcessingErr> {
+    let Some(config_provider) = ActorRef::where_is("config_provider".into()) else {
+        return Ok(());
+    };
+    let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+    let Some(moderator) = ActorRef::where_is("moderator".into()) else {
+        error!("Unable to find moderator");
+        return Ok(());
+    };
+    let my_id = state.client.user_id();
+    for room in state.client.joined_rooms() {
+        let room_id = room.room_id();
+        if !config.is_protected_room(room_id.as_str()) {
+            continue;
+        }
+        if let Some(rule) = state.match_room(room_id) {
+            warn!(room = %room_id, reason = rule.reason, "protected room is on a policy list");
+        }
+        for member in room.members(RoomMemberships::JOIN).await? {
+            if Some(member.user_id()) == my_id {
+                continue;
+            }
+            if let Some(rule) = state.match_user(member.user_id()) {
+                info!(
+                    user = %member.user_id(),
+                    room = %room_id,
+                    reason = rule.reason,
+                    "member matches policy list"
+                );
+                ractor::cast!(
+                    moderator,
+                    ModeratorMessage::Violation {
+                        user_room_id: UserRoomId {
+                            user_id: member.user_id().to_owned(),
+                            room_id: room_id.to_owned(),
+                        },
+                        monitor: "policy_list",
+                        kind: ViolationKind::PolicyList,
+                        evidence: vec![],
+                        sanction: Sanction::Ban,
+                    }
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Joins the policy room unless the bot is already in it.
+async fn join(client: &Client, room_id: &str) -> Result<OwnedRoomId, ActorProcessingErr> {
+    let room_id = RoomId::parse(room_id)?;
+    if client.get_room(&room_id).is_none() {
+        client.join_room_by_id(&room_id).await?;
+    }
+    Ok(room_id)
+}
+
+/// Joins and loads the subscribed policy rooms that are not loaded yet.
+async fn subscribe(state: &mut PolicyListState) {
+    for room_id in state.config.subscribe.clone() {
+        if state.rules.keys().any(|loaded| loaded.as_str() == room_id) {
+            continue;
+        }
+        match join(&state.client, &room_id).await {
+            Ok(room_id) => {
+                if let Err(error) = reload(state, &room_id).await {
+                    error!(room = %room_id, ?error, "Unable to load policy list");
+                }
+            }
+            Err(error) => error!(room = room_id, ?error, "Unable to join policy room"),
+        }
+    }
+}
+
+/// Returns the reason the user is banned by a subscribed policy list.
+pub(crate) async fn policy_match(user_id: &UserId) -> Result<Option<String>, ActorProcessingErr> {
+    if let Some(policy_list) = ActorRef::<PolicyListMessage>::where_is("policy_list".into()) {
+        return Ok(ractor::call!(
+            policy_list,
+            PolicyListMessage::Match,
+            user_id.to_owned()
+        )?);
+    }
+    Ok(None)
+}
+
+impl Actor for PolicyList {
+    type Msg = PolicyListMessage;
+    type State = PolicyListState;
+    type Arguments = Client;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(PolicyListState {
+            client: args,
+            config: Default::default(),
+            rules: HashMap::new(),
+            changed: HashSet::new(),
+        })
+    }
+
+    async fn post_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+            let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+            state.config = config.policy_lists.unwrap_or_default();
+        }
+        subscribe(state).await;
+        if let Some(publish) = &state.config.publish
+            && let Err(error) = join(&state.client, publish).await
+        {
+            error!(room = publish, ?error, "Unable to join policy room");
+        }
+        if let Err(error) = enforce(state).await {
+            error!(?error, "Unable to enforce policy lists");
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            PolicyListMessage::Reload(room_id) => {
+                if !state
+                    .config
+                    .subscribe
+                    .iter()
+                    .any(|id| id == room_id.as_str())
+                {
+                    return Ok(());
+                }
+                if state.changed.is_empty() {
+                    myself.send_after(ENFORCE_DELAY, || PolicyListMessage::Enforce);
+                }
+                state.changed.insert(room_id);
+            }
+            PolicyListMessage::Enforce => {
+                for room_id in std::mem::take(&mut state.changed) {
+                    if let Err(error) = reload(state, &room_id).await {
+                        error!(room = %room_id, ?error, "Unable to load policy list");
+                    }
+                }
+                // Rooms that failed to join or load on start are retried
+                subscribe(state).await;
+                enforce(state).await?;
+            }
+            PolicyListMessage::Match(user_id, reply) => {
+                let reason = state.match_user(&user_id).map(|rule| rule.reason.clone());
+                reply.send(reason)?;
+            }
+            PolicyListMessage::Publish {
+                user_id,
+                kind,
+                reason,
+            } => {
+                if let Some(publish) = &state.config.publish
+                    && state.config.publish_kinds.iter().any(|name| name == kind)
+                    && let Some(room) = state.client.get_room(&RoomId::parse(publish)?)
+                {
+                    info!(user = %user_id, room = %publish, "publishing ban to policy list");
+                    let content = PolicyRuleUserEventContent(PolicyRuleEventContent::new(
+                        user_id.to_string(),
+                        Recommendation::Ban,
+                        reason,
+                    ));
+                    room.send_state_event_for_key(&format!("rule:{user_id}"), content)
+                        .await?;
+                }
+            }
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_literal() {
+        assert!(glob_match("@spam:example.org", "@spam:example.org"));
+        assert!(!glob_match("@spam:example.org", "@spam:example.com"));
+        assert!(!glob_match("@spam", "@spammer"));
+        assert!(glob_match("", ""));
+        assert!(!glob_match("", "a"));
+    }
+
+    #[test]
+    fn glob_match_wildcards() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("*.example.org", "matrix.example.org"));
+        assert!(!glob_match("*.example.org", "example.org"));
+        assert!(glob_match("@*:evil.com", "@bot123:evil.com"));
+        assert!(glob_match("@spam?:example.org", "@spam1:example.org"));
+        assert!(!glob_match("@spam?:example.org", "@spam:example.org"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(!glob_match("a*b*c", "aXbYbZ"));
+        assert!(glob_match("**a", "bba"));
+        assert!(glob_match("ü?*", "üß"));
+    }
+}
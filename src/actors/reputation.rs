@@ -0,0 +1,299 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use matrix_sdk::{
+    ruma::{OwnedUserId, UserId},
+    Client,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef, RpcReplyPort};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::matrix::UserRoomId;
+
+use super::config_provider::ConfigProviderMessage;
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Changed records are written to the state store this often
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Records kept in memory, the cache is emptied after a flush above it
+const MAX_CACHED_RECORDS: usize = 10_000;
+
+/// Persistent track record of a user across all rooms
+pub(crate) struct Reputation;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ReputationEvent {
+    /// The user joined or spoke in a room
+    Seen,
+    Message,
+    CaptchaPassed,
+    Violation,
+    /// A moderator vouched for the user
+    Vouch,
+    /// A moderator pardoned the user, forgetting their violations
+    Pardon,
+}
+
+pub(crate) enum ReputationMessage {
+    Record(OwnedUserId, ReputationEvent),
+    GetScore(OwnedUserId, RpcReplyPort<i64>),
+    /// Writes the changed records to the state store
+    Flush,
+}
+
+/// How monitors treat a user
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Standing {
+    /// Skips the captcha and starts with the relaxed rate limit
+    Trusted,
+    Neutral,
+    /// Stays on the new member rate limit
+    Untrusted,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ReputationRecord {
+    /// Unix time the user was first seen by the bot
+    first_seen_secs: u64,
+    messages: u64,
+    captchas_passed: u32,
+    violations: u32,
+    vouches: u32,
+}
+
+impl ReputationRecord {
+    fn score(&self, now_secs: u64) -> i64 {
+        let days = now_secs.saturating_sub(self.first_seen_secs) / SECS_PER_DAY;
+        days.min(30) as i64
+            + (self.messages / 10).min(50) as i64
+            + 5 * i64::from(self.captchas_passed)
+            + 20 * i64::from(self.vouches)
+            - 25 * i64::from(self.violations)
+    }
+}
+
+pub(crate) struct ReputationState {
+    client: Client,
+    records: HashMap<OwnedUserId, ReputationRecord>,
+    /// Users whose record changed since the last flush
+    changed: HashSet<OwnedUserId>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn record_key(user_id: &OwnedUserId) -> Vec<u8> {
+    format!("t1bot.reputation.{user_id}").into_bytes()
+}
+
+async fn load<'a>(
+    state: &'a mut ReputationState,
+    user_id: &OwnedUserId,
+) -> Result<&'a mut ReputationRecord, ActorProcessingErr> {
+    if !state.records.contains_key(user_id) {
+        let record = match state
+            .client
+            .state_store()
+            .get_custom_value(&record_key(user_id))
+            .await?
+        {
+            Some(value) => serde_json::from_slice(&value)?,
+            None => ReputationRecord {
+                first_seen_secs: now_secs(),
+                ..Default::default()
+            },
+        };
+        state.records.insert(user_id.clone(), record);
+    }
+    Ok(state.records.entry(user_id.clone()).or_default())
+}
+
+async fn flush(state: &mut ReputationState) -> Result<(), ActorProcessingErr> {
+    for user_id in state.changed.drain() {
+        if let Some(record) = state.records.get(&user_id) {
+            let value = serde_json::to_vec(record)?;
+            if let Err(error) = state
+                .client
+                .state_store()
+                .set_custom_value(&record_key(&user_id), value)
+                .await
+            {
+                error!(user = %user_id, ?error, "Unable to save reputation");
+            }
+        }
+    }
+    if state.records.len() > MAX_CACHED_RECORDS {
+        state.records.clear();
+    }
+    Ok(())
+}
+
+/// Looks up how monitors should treat the user in the room. Users are neutral
+/// unless reputation is configured for the room.
+pub(crate) async fn standing(user_room_id: &UserRoomId) -> Result<Standing, ActorProcessingErr> {
+    let Some(config_provider) = ActorRef::where_is("config_provider".into()) else {
+        return Ok(Standing::Neutral);
+    };
+    let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+    let Some(reputation_config) = config.monitor(user_room_id.room_id.as_str(), |m| &m.reputation)
+    else {
+        return Ok(Standing::Neutral);
+    };
+    let Some(reputation) = ActorRef::<ReputationMessage>::where_is("reputation".into()) else {
+        return Ok(Standing::Neutral);
+    };
+    let score = ractor::call!(
+        reputation,
+        ReputationMessage::GetScore,
+        user_room_id.user_id.clone()
+    )?;
+    Ok(if score >= reputation_config.trusted_score {
+        Standing::Trusted
+    } else if score < reputation_config.untrusted_score {
+        Standing::Untrusted
+    } else {
+        Standing::Neutral
+    })
+}
+
+/// Records the event in the reputation of the user.
+pub(crate) fn record(user_id: &UserId, event: ReputationEvent) -> Result<(), ActorProcessingErr> {
+    if let Some(reputation) = ActorRef::<ReputationMessage>::where_is("reputation".into()) {
+        ractor::cast!(
+            reputation,
+            ReputationMessage::Record(user_id.to_owned(), event)
+        )?;
+    }
+    Ok(())
+}
+
+impl Actor for Reputation {
+    type Msg = ReputationMessage;
+    type State = ReputationState;
+    type Arguments = Client;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(ReputationState {
+            client: args,
+            records: HashMap::new(),
+            changed: HashSet::new(),
+        })
+    }
+
+    async fn post_start(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        _state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        myself.send_after(FLUSH_INTERVAL, || ReputationMessage::Flush);
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            ReputationMessage::Record(user_id, event) => {
+                let record = load(state, &user_id).await?;
+                match event {
+                    ReputationEvent::Seen => {}
+                    ReputationEvent::Message => record.messages += 1,
+                    ReputationEvent::CaptchaPassed => record.captchas_passed += 1,
+                    ReputationEvent::Violation => record.violations += 1,
+                    ReputationEvent::Vouch => {
+                        info!(user = %user_id, "moderator vouched for user");
+                        record.vouches += 1;
+                    }
+                    ReputationEvent::Pardon => {
+                        info!(user = %user_id, "moderator pardoned user");
+                        record.violations = 0;
+                    }
+                }
+                // Every message would otherwise be a write to the store
+                state.changed.insert(user_id);
+            }
+            ReputationMessage::GetScore(user_id, reply) => {
+                let record = load(state, &user_id).await?;
+                reply.send(record.score(now_secs()))?;
+            }
+            ReputationMessage::Flush => {
+                flush(state).await?;
+                myself.send_after(FLUSH_INTERVAL, || ReputationMessage::Flush);
+            }
+        };
+        Ok(())
+    }
+
+    async fn post_stop(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        flush(state).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_000 * SECS_PER_DAY;
+
+    fn seen_days_ago(days: u64) -> ReputationRecord {
+        ReputationRecord {
+            first_seen_secs: NOW - days * SECS_PER_DAY,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn score_new_user() {
+        assert_eq!(seen_days_ago(0).score(NOW), 0);
+        // Clocks going back do not make the score negative
+        assert_eq!(seen_days_ago(0).score(NOW - SECS_PER_DAY), 0);
+    }
+
+    #[test]
+    fn score_caps_age_and_messages() {
+        assert_eq!(seen_days_ago(7).score(NOW), 7);
+        assert_eq!(seen_days_ago(365).score(NOW), 30);
+        let chatty = ReputationRecord {
+            messages: 10_000,
+            ..seen_days_ago(0)
+        };
+        assert_eq!(chatty.score(NOW), 50);
+        let quiet = ReputationRecord {
+            messages: 19,
+            ..seen_days_ago(0)
+        };
+        assert_eq!(quiet.score(NOW), 1);
+    }
+
+    #[test]
+    fn score_weights() {
+        let user = ReputationRecord {
+            messages: 100,
+            captchas_passed: 2,
+            vouches: 1,
+            violations: 3,
+            ..seen_days_ago(10)
+        };
+        assert_eq!(user.score(NOW), 10 + 10 + 2 * 5 + 20 - 3 * 25);
+    }
+}
@@ -0,0 +1,167 @@
+use matrix_sdk::{
+    ruma::{OwnedRoomId, RoomId},
+    Client, Room, RoomMemberships, RoomState,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use tracing::{error, info, warn};
+
+use crate::matrix::UserRoomId;
+
+use super::{
+    config_provider::ConfigProviderMessage, moderator::ModeratorMessage, monitor::copy_onboarded,
+};
+
+/// Follows protected rooms to the room that replaces them when they are
+/// upgraded.
+pub(crate) struct RoomUpgrade;
+
+pub(crate) enum RoomUpgradeMessage {
+    /// The room was replaced by a new room
+    Tombstone {
+        room_id: OwnedRoomId,
+        replacement_room: OwnedRoomId,
+    },
+}
+
+/// Carries the bans and onboarded members of the old room over to the new
+/// room. Reputation is kept per user and needs no migration.
+async fn migrate(client: &Client, old: &Room, new: &Room) -> Result<(), ActorProcessingErr> {
+    let banned = old.members(RoomMemberships::BAN).await?;
+    // The moderator applies the mode and permissions of the new room
+    if let Some(moderator) = ActorRef::where_is("moderator".into()) {
+        for member in &banned {
+            ractor::cast!(
+                moderator,
+                ModeratorMessage::Ban {
+                    user_room_id: UserRoomId {
+                        user_id: member.user_id().to_owned(),
+                        room_id: new.room_id().to_owned(),
+                    },
+                    reason: member.event().reason().unwrap_or_default().to_string(),
+                }
+            )?;
+        }
+    } else {
+        error!("Unable to find moderator");
+    }
+    for member in old.members(RoomMemberships::JOIN).await? {
+        let user_room_id = |room_id: &RoomId| UserRoomId {
+            user_id: member.user_id().to_owned(),
+            room_id: room_id.to_owned(),
+        };
+        copy_onboarded(
+            client,
+            &user_room_id(old.room_id()),
+            &user_room_id(new.room_id()),
+        )
+        .await?;
+    }
+    info!(
+        old = %old.room_id(),
+        new = %new.room_id(),
+        bans = banned.len(),
+        "migrated upgraded room"
+    );
+    Ok(())
+}
+
+/// Key of the custom value recording that the upgrade was migrated
+fn migrated_key(old: &RoomId, new: &RoomId) -> Vec<u8> {
+    format!("t1bot.migrated.{old}.{new}").into_bytes()
+}
+
+/// Joins the replacement room of an upgraded room, migrating its state once,
+/// and applies the config of the old room to it.
+async fn follow(
+    client: &Client,
+    room_id: &RoomId,
+    replacement_room: &RoomId,
+) -> Result<(), ActorProcessingErr> {
+    let Some(config_provider) = ActorRef::where_is("config_provider".into()) else {
+        return Ok(());
+    };
+    let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+    if !config.is_room_enabled(room_id.as_str()) {
+        return Ok(());
+    }
+    let Some(old) = client.get_room(room_id) else {
+        return Ok(());
+    };
+
+    ractor::cast!(
+        config_provider,
+        ConfigProviderMessage::SetRoomReplacement {
+            old: room_id.to_string(),
+            new: replacement_room.to_string(),
+        }
+    )?;
+    warn!(
+        old = %room_id,
+        new = %replacement_room,
+        "room was upgraded, replace [rooms.\"{}\"] with [rooms.\"{replacement_room}\"] \
+        in the config",
+        config.room_key(room_id.as_str())
+    );
+
+    // The bot may already have been invited to or have joined the new room,
+    // whether the upgrade was migrated is recorded separately
+    let new = match client.get_room(replacement_room) {
+        Some(new) if new.state() == RoomState::Joined => new,
+        _ => {
+            info!(old = %room_id, new = %replacement_room, "joining replacement room");
+            client.join_room_by_id(replacement_room).await?
+        }
+    };
+    let key = migrated_key(room_id, replacement_room);
+    if client.state_store().get_custom_value(&key).await?.is_none() {
+        migrate(client, &old, &new).await?;
+        client.state_store().set_custom_value(&key, vec![1]).await?;
+    }
+    Ok(())
+}
+
+impl Actor for RoomUpgrade {
+    type Msg = RoomUpgradeMessage;
+    type State = Client;
+    type Arguments = Client;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(args)
+    }
+
+    async fn post_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        // Upgrades are only kept in memory, find them again on start
+        for room in state.joined_rooms() {
+            if let Some(tombstone) = room.tombstone_content()
+                && let Err(error) = follow(state, room.room_id(), &tombstone.replacement_room).await
+            {
+                // Returning the error would restart the actor into it again
+                error!(room = %room.room_id(), ?error, "Unable to follow upgraded room");
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            RoomUpgradeMessage::Tombstone {
+                room_id,
+                replacement_room,
+            } => follow(state, &room_id, &replacement_room).await?,
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use matrix_sdk::{
+    deserialized_responses::SyncOrStrippedState,
+    ruma::{
+        events::{room::server_acl::RoomServerAclEventContent, SyncStateEvent},
+        OwnedServerName, OwnedUserId, ServerName, UserId,
+    },
+    Client, Room,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef, RpcReplyPort};
+use tracing::{info, warn};
+
+use crate::config::ServerAclConfig;
+
+use super::{
+    config_provider::ConfigProviderMessage, moderator::not_enforced_in, policy_list::glob_match,
+};
+
+/// Maintains the server ACL of the protected rooms.
+pub(crate) struct ServerAcl;
+
+pub(crate) enum ServerAclMessage {
+    /// A user was sanctioned, their server is denied once too many of its
+    /// accounts were
+    Violation(OwnedUserId),
+    /// Denies the server in every protected room. Replies whether the server
+    /// was denied, allowlisted servers are not.
+    Deny(OwnedServerName, RpcReplyPort<bool>),
+    /// Removes the server from the deny list of every protected room
+    Remove(OwnedServerName, RpcReplyPort<()>),
+    /// Forgets the violations of a pardoned user
+    Pardon(OwnedUserId),
+}
+
+pub(crate) struct ServerAclState {
+    client: Client,
+    /// Recent violations per server
+    violations: HashMap<OwnedServerName, Vec<(OwnedUserId, Instant)>>,
+}
+
+async fn server_acl_config() -> Result<Option<ServerAclConfig>, ActorProcessingErr> {
+    if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+        let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+        return Ok(config.server_acl);
+    }
+    Ok(None)
+}
+
+/// Rooms whose server ACL is maintained by the bot
+async fn protected_rooms(client: &Client) -> Result<Vec<Room>, ActorProcessingErr> {
+    let Some(config_provider) = ActorRef::where_is("config_provider".into()) else {
+        return Ok(vec![]);
+    };
+    let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+    Ok(client
+        .joined_rooms()
+        .into_iter()
+        .filter(|room| config.is_protected_room(room.room_id().as_str()))
+        .collect())
+}
+
+/// Applies the change to the server ACL of the room, starting from an ACL that
+/// allows every server if the room has none.
+async fn update_acl(
+    room: &Room,
+    change: impl Fn(&mut RoomServerAclEventContent) -> bool,
+) -> Result<(), ActorProcessingErr> {
+    let mut content = match room
+        .get_state_event_static::<RoomServerAclEventContent>()
+        .await?
+        .map(|raw| raw.deserialize())
+    {
+        Some(Ok(SyncOrStrippedState::Sync(SyncStateEvent::Original(ev)))) => ev.content,
+        _ => RoomServerAclEventContent::new(true, vec!["*".to_string()], vec![]),
+    };
+    if change(&mut content) {
+        room.send_state_event(content).await?;
+    }
+    Ok(())
+}
+
+/// Records the violation of the user, forgetting the violations older than
+/// the window. Returns the number of accounts of the server with recent
+/// violations.
+fn record_violation(
+    violations: &mut Vec<(OwnedUserId, Instant)>,
+    user_id: &UserId,
+    now: Instant,
+    window: Duration,
+) -> usize {
+    violations.retain(|(_, at)| now.duration_since(*at) < window);
+    if !violations.iter().any(|(id, _)| id == user_id) {
+        violations.push((user_id.to_owned(), now));
+    }
+    violations.len()
+}
+
+/// Whether the server must never be denied: the server of the bot and the
+/// servers matching the allowlist.
+fn is_allowlisted(
+    config: &ServerAclConfig,
+    own_server: Option<&ServerName>,
+    server_name: &ServerName,
+) -> bool {
+    own_server == Some(server_name)
+        || config
+            .allow
+            .iter()
+            .any(|allowed| glob_match(allowed, server_name.as_str()))
+}
+
+async fn deny(
+    client: &Client,
+    config: &ServerAclConfig,
+    server_name: &ServerName,
+) -> Result<bool, ActorProcessingErr> {
+    let own_server = client.user_id().map(|user_id| user_id.server_name());
+    if is_allowlisted(config, own_server, server_name) {
+        warn!(server = %server_name, "refusing to deny allowlisted server");
+        return Ok(false);
+    }
+    for room in protected_rooms(client).await? {
+        if not_enforced_in(
+            client,
+            room.room_id(),
+            "log-report-server-acl",
+            [
+                ("server", server_name.to_string().into()),
+                ("room", room.room_id().to_string().into()),
+            ],
+        )
+        .await?
+        .is_some()
+        {
+            continue;
+        }
+        info!(server = %server_name, room = %room.room_id(), "denying server");
+        update_acl(&room, |content| {
+            let denied = content
+                .deny
+                .iter()
+                .any(|entry| entry == server_name.as_str());
+            if !denied {
+                content.deny.push(server_name.to_string());
+            }
+            !denied
+        })
+        .await?;
+    }
+    Ok(true)
+}
+
+impl Actor for ServerAcl {
+    type Msg = ServerAclMessage;
+    type State = ServerAclState;
+    type Arguments = Client;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(ServerAclState {
+            client: args,
+            violations: HashMap::new(),
+        })
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            ServerAclMessage::Violation(user_id) => {
+                let Some(config) = server_acl_config().await? else {
+                    return Ok(());
+                };
+                let window = Duration::from_secs(config.window_secs);
+                let violations = state
+                    .violations
+                    .entry(user_id.server_name().to_owned())
+                    .or_default();
+                let accounts = record_violation(violations, &user_id, Instant::now(), window);
+                if accounts >= config.max_violations {
+                    info!(
+                        server = %user_id.server_name(),
+                        accounts,
+                        "too many violations from server"
+                    );
+                    violations.clear();
+                    deny(&state.client, &config, user_id.server_name()).await?;
+                }
+            }
+            ServerAclMessage::Deny(server_name, reply) => {
+                let config = server_acl_config().await?.unwrap_or_default();
+                let denied = deny(&state.client, &config, &server_name).await?;
+                reply.send(denied)?;
+            }
+            ServerAclMessage::Remove(server_name, reply) => {
+                for room in protected_rooms(&state.client).await? {
+                    info!(
+                        server = %server_name,
+                        room = %room.room_id(),
+                        "removing server from deny list"
+                    );
+                    update_acl(&room, |content| {
+                        let count = content.deny.len();
+                        content.deny.retain(|entry| entry != server_name.as_str());
+                        content.deny.len() != count
+                    })
+                    .await?;
+                }
+                reply.send(())?;
+            }
+            ServerAclMessage::Pardon(user_id) => {
+                if let Some(violations) = state.violations.get_mut(user_id.server_name()) {
+                    violations.retain(|(id, _)| *id != user_id);
+                }
+            }
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk::ruma::{server_name, user_id};
+
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn record_violation_counts_accounts_once() {
+        let now = Instant::now();
+        let mut violations = vec![];
+        assert_eq!(
+            record_violation(&mut violations, user_id!("@a:spam.org"), now, WINDOW),
+            1
+        );
+        assert_eq!(
+            record_violation(&mut violations, user_id!("@a:spam.org"), now, WINDOW),
+            1
+        );
+        assert_eq!(
+            record_violation(&mut violations, user_id!("@b:spam.org"), now, WINDOW),
+            2
+        );
+    }
+
+    #[test]
+    fn record_violation_forgets_old_violations() {
+        let now = Instant::now();
+        let mut violations = vec![];
+        record_violation(&mut violations, user_id!("@a:spam.org"), now, WINDOW);
+        record_violation(
+            &mut violations,
+            user_id!("@b:spam.org"),
+            now + WINDOW / 2,
+            WINDOW,
+        );
+        assert_eq!(
+            record_violation(
+                &mut violations,
+                user_id!("@c:spam.org"),
+                now + WINDOW,
+                WINDOW
+            ),
+            2
+        );
+        assert_eq!(
+            record_violation(
+                &mut violations,
+                user_id!("@d:spam.org"),
+                now + WINDOW * 3,
+                WINDOW
+            ),
+            1
+        );
+    }
+
+    #[test]
+    fn allowlist() {
+        let config = ServerAclConfig {
+            allow: vec!["example.org".into(), "*.example.com".into()],
+            ..Default::default()
+        };
+        let own = Some(server_name!("bot.org"));
+        assert!(is_allowlisted(&config, own, server_name!("bot.org")));
+        assert!(is_allowlisted(&config, own, server_name!("example.org")));
+        assert!(is_allowlisted(
+            &config,
+            own,
+            server_name!("matrix.example.com")
+        ));
+        assert!(!is_allowlisted(&config, own, server_name!("example.com")));
+        assert!(!is_allowlisted(&config, own, server_name!("spam.org")));
+        assert!(!is_allowlisted(&config, None, server_name!("bot.org")));
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+use matrix_sdk::{
+    deserialized_responses::RawAnySyncOrStrippedState,
+    ruma::{
+        events::{space::child::SpaceChildEventContent, StateEventType},
+        OwnedRoomId, OwnedServerName, RoomId, RoomOrAliasId,
+    },
+    Client, Room,
+};
+use ractor::{Actor, ActorProcessingErr, ActorRef};
+use tracing::{error, info};
+
+use super::config_provider::ConfigProviderMessage;
+
+/// Protects the child rooms of the configured spaces.
+pub(crate) struct Space;
+
+pub(crate) enum SpaceMessage {
+    /// Rediscovers the children of the space after they changed
+    Refresh(OwnedRoomId),
+}
+
+pub(crate) struct SpaceState {
+    client: Client,
+    /// Config key of every configured space
+    spaces: HashMap<OwnedRoomId, String>,
+}
+
+/// Reads the child rooms of the space. Children whose `via` is empty or whose
+/// content was cleared were removed from the space.
+async fn read_children(
+    room: &Room,
+) -> Result<Vec<(OwnedRoomId, Vec<OwnedServerName>)>, ActorProcessingErr> {
+    let mut children = vec![];
+    for raw in room.get_state_events(StateEventType::SpaceChild).await? {
+        if let RawAnySyncOrStrippedState::Sync(raw) = raw
+            && let Ok(Some(child)) = raw.get_field::<OwnedRoomId>("state_key")
+            && let Ok(Some(content)) = raw.get_field::<SpaceChildEventContent>("content")
+            && !content.via.is_empty()
+        {
+            children.push((child, content.via));
+        }
+    }
+    Ok(children)
+}
+
+async fn refresh(state: &mut SpaceState, space_id: &RoomId) -> Result<(), ActorProcessingErr> {
+    let Some(space) = state.spaces.get(space_id).cloned() else {
+        return Ok(());
+    };
+    let Some(room) = state.client.get_room(space_id) else {
+        return Ok(());
+    };
+    let Some(config_provider) = ActorRef::where_is("config_provider".into()) else {
+        return Ok(());
+    };
+    let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+
+    let children = read_children(&room).await?;
+    for (child, via) in &children {
+        if state.client.get_room(child).is_some() {
+            continue;
+        }
+        info!(space = %space_id, room = %child, "joining space child");
+        if let Err(error) = state
+            .client
+            .join_room_by_id_or_alias(<&RoomOrAliasId>::from(&**child), via)
+            .await
+        {
+            error!(space = %space_id, room = %child, ?error, "Unable to join space child");
+        }
+    }
+    let children: Vec<String> = children
+        .into_iter()
+        .map(|(child, _)| child.to_string())
+        .collect();
+
+    // Rooms that left the space are no longer protected, unless listed on
+    // their own. The children found by the last refresh are kept by the config
+    // provider, the single source of truth.
+    for removed in config
+        .space_children
+        .iter()
+        .filter(|(child, parent)| {
+            **parent == space && !children.contains(child) && !config.rooms.contains_key(*child)
+        })
+        .filter_map(|(child, _)| RoomId::parse(child).ok())
+    {
+        if let Some(room) = state.client.get_room(&removed) {
+            info!(space = %space_id, room = %removed, "leaving room removed from space");
+            if let Err(error) = room.leave().await {
+                error!(space = %space_id, room = %removed, ?error, "Unable to leave room");
+            }
+        }
+    }
+
+    info!(space = %space_id, count = children.len(), "discovered space children");
+    ractor::cast!(
+        config_provider,
+        ConfigProviderMessage::SetSpaceChildren { space, children }
+    )?;
+    Ok(())
+}
+
+async fn resolve(client: &Client, space: &str) -> Result<OwnedRoomId, ActorProcessingErr> {
+    Ok(match OwnedRoomId::try_from(RoomOrAliasId::parse(space)?) {
+        Ok(room_id) => room_id,
+        Err(alias) => client.resolve_room_alias(&alias).await?.room_id,
+    })
+}
+
+impl Actor for Space {
+    type Msg = SpaceMessage;
+    type State = SpaceState;
+    type Arguments = Client;
+
+    async fn pre_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        args: Self::Arguments,
+    ) -> Result<Self::State, ActorProcessingErr> {
+        Ok(SpaceState {
+            client: args,
+            spaces: HashMap::new(),
+        })
+    }
+
+    async fn post_start(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        let Some(config_provider) = ActorRef::where_is("config_provider".into()) else {
+            return Ok(());
+        };
+        let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+        for space in config.spaces() {
+            match resolve(&state.client, space).await {
+                Ok(space_id) => {
+                    state.spaces.insert(space_id, space.to_string());
+                }
+                Err(error) => error!(space, ?error, "Unable to resolve space"),
+            }
+        }
+        for space_id in state.spaces.keys().cloned().collect::<Vec<_>>() {
+            if let Err(error) = refresh(state, &space_id).await {
+                error!(space = %space_id, ?error, "Unable to refresh space");
+            }
+        }
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        _myself: ActorRef<Self::Msg>,
+        message: Self::Msg,
+        state: &mut Self::State,
+    ) -> Result<(), ActorProcessingErr> {
+        match message {
+            SpaceMessage::Refresh(space_id) => refresh(state, &space_id).await?,
+        };
+        Ok(())
+    }
+}
@@ -1,21 +1,95 @@
-use matrix_sdk::Client;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use matrix_sdk::{ruma::OwnedUserId, Client};
 use ractor::{registry, Actor, ActorProcessingErr, ActorRef, SupervisionEvent};
 use tracing::{error, info};
 
-use crate::matrix::UserRoomId;
+use crate::{config::Sanction, matrix::UserRoomId};
+
+use super::{
+    config_provider::ConfigProviderMessage,
+    moderator::{notify_log_room, ModeratorMessage, ViolationKind},
+    monitor::{
+        CaptchaAdmission, CaptchaInit, CaptchaMonitor, Monitor, MonitorInit, MonitorMessage,
+    },
+    policy_list::policy_match,
+};
+
+/// Bans the user if a subscribed policy list bans them. Returns whether the
+/// user was banned.
+async fn ban_listed_user(user_room_id: &UserRoomId) -> Result<bool, ActorProcessingErr> {
+    let Some(reason) = policy_match(&user_room_id.user_id).await? else {
+        return Ok(false);
+    };
+    info!(user = %user_room_id, reason, "user matches policy list");
+    if let Some(moderator) = ActorRef::where_is("moderator".into()) {
+        ractor::cast!(
+            moderator,
+            ModeratorMessage::Violation {
+                user_room_id: user_room_id.clone(),
+                monitor: "policy_list",
+                kind: ViolationKind::PolicyList,
+                evidence: vec![],
+                sanction: Sanction::Ban,
+            }
+        )?;
+    } else {
+        error!("Unable to find moderator");
+    }
+    Ok(true)
+}
 
-use super::monitor::{Monitor, MonitorInit};
+/// Invites that were not accepted in this time are forgotten.
+const PENDING_INVITE_TIMEOUT: Duration = Duration::from_secs(60 * 60 * 24);
 
 pub(crate) struct Spawner;
 
+pub(crate) struct SpawnerState {
+    client: Client,
+    /// Inviter of users who were invited but did not join yet
+    pending_invites: HashMap<String, (OwnedUserId, Instant)>,
+    /// Users who passed the captcha after knocking and were invited
+    admitted: HashMap<String, Instant>,
+}
+
 pub(crate) enum SpawnerMessage {
     RegisterUser(UserRoomId),
     RegisterUserJoin(UserRoomId),
+    Invite {
+        inviter: UserRoomId,
+        invitee: OwnedUserId,
+    },
+    /// The user knocked on a room, challenge them in a DM before letting them in
+    Knock(UserRoomId),
+    /// The user who knocked passed the captcha and was invited
+    Admitted(UserRoomId),
+}
+
+async fn is_room_enabled(user_room_id: &UserRoomId) -> Result<bool, ActorProcessingErr> {
+    if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+        let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+        return Ok(config.is_room_enabled(user_room_id.room_id.as_str()));
+    }
+    Ok(true)
+}
+
+async fn is_captcha_enabled(user_room_id: &UserRoomId) -> Result<bool, ActorProcessingErr> {
+    if let Some(config_provider) = ActorRef::where_is("config_provider".into()) {
+        let config = ractor::call!(config_provider, ConfigProviderMessage::GetConfig)?;
+        let room_id = user_room_id.room_id.as_str();
+        return Ok(
+            config.is_room_enabled(room_id) && config.monitor(room_id, |m| &m.captcha).is_some()
+        );
+    }
+    Ok(false)
 }
 
 impl Actor for Spawner {
     type Msg = SpawnerMessage;
-    type State = Client;
+    type State = SpawnerState;
     type Arguments = Client;
 
     async fn pre_start(
@@ -23,7 +97,11 @@ impl Actor for Spawner {
         _myself: ActorRef<Self::Msg>,
         args: Self::Arguments,
     ) -> Result<Self::State, ActorProcessingErr> {
-        Ok(args)
+        Ok(SpawnerState {
+            client: args,
+            pending_invites: HashMap::new(),
+            admitted: HashMap::new(),
+        })
     }
 
     async fn handle(
@@ -34,27 +112,138 @@ impl Actor for Spawner {
     ) -> Result<(), ActorProcessingErr> {
         match message {
             SpawnerMessage::RegisterUser(user_room_id) => {
-                if registry::where_is(user_room_id.to_string()).is_none() {
+                if registry::where_is(user_room_id.to_string()).is_none()
+                    && is_room_enabled(&user_room_id).await?
+                {
                     Actor::spawn_linked(
                         Some(user_room_id.to_string()),
                         Monitor,
-                        (user_room_id, state.clone(), MonitorInit::Msg),
+                        (user_room_id, state.client.clone(), MonitorInit::Msg),
                         myself.into(),
                     )
                     .await?;
                 }
             }
             SpawnerMessage::RegisterUserJoin(user_room_id) => {
-                if registry::where_is(user_room_id.to_string()).is_none() {
+                let invited_by = state
+                    .pending_invites
+                    .remove(&user_room_id.to_string())
+                    .map(|(inviter, _)| inviter);
+                let admitted = state
+                    .admitted
+                    .remove(&user_room_id.to_string())
+                    .is_some_and(|admitted_at| admitted_at.elapsed() < PENDING_INVITE_TIMEOUT);
+                if registry::where_is(user_room_id.to_string()).is_none()
+                    && is_room_enabled(&user_room_id).await?
+                    && !ban_listed_user(&user_room_id).await?
+                {
+                    if let Some(inviter) = &invited_by {
+                        notify_log_room(
+                            &state.client,
+                            "log-invited-join",
+                            [
+                                ("user", user_room_id.user_id.to_string().into()),
+                                ("room", user_room_id.room_id.to_string().into()),
+                                ("inviter", inviter.to_string().into()),
+                            ],
+                        )
+                        .await?;
+                    }
                     Actor::spawn_linked(
                         Some(user_room_id.to_string()),
                         Monitor,
-                        (user_room_id, state.clone(), MonitorInit::Join),
+                        (
+                            user_room_id,
+                            state.client.clone(),
+                            MonitorInit::Join {
+                                invited_by,
+                                admitted,
+                            },
+                        ),
                         myself.into(),
                     )
                     .await?;
                 }
             }
+            SpawnerMessage::Invite { inviter, invitee } => {
+                if !is_room_enabled(&inviter).await? {
+                    return Ok(());
+                }
+                let invitee_room_id = UserRoomId {
+                    user_id: invitee.clone(),
+                    room_id: inviter.room_id.clone(),
+                };
+                info!(user = %invitee_room_id, inviter = %inviter.user_id, "user was invited");
+                state
+                    .pending_invites
+                    .retain(|_, (_, invited_at)| invited_at.elapsed() < PENDING_INVITE_TIMEOUT);
+                state.pending_invites.insert(
+                    invitee_room_id.to_string(),
+                    (inviter.user_id.clone(), Instant::now()),
+                );
+                // Invites of room moderators are recorded but not limited
+                if let Some(room) = state.client.get_room(&inviter.room_id)
+                    && let Some(member) = room.get_member(&inviter.user_id).await?
+                    && (member.can_kick() || member.can_ban())
+                {
+                    return Ok(());
+                }
+                let monitor = match ActorRef::<MonitorMessage>::where_is(inviter.to_string()) {
+                    Some(monitor) => monitor,
+                    None => {
+                        Actor::spawn_linked(
+                            Some(inviter.to_string()),
+                            Monitor,
+                            (inviter, state.client.clone(), MonitorInit::Msg),
+                            myself.get_cell(),
+                        )
+                        .await?
+                        .0
+                    }
+                };
+                ractor::cast!(monitor, MonitorMessage::Invite(invitee))?;
+            }
+            SpawnerMessage::Knock(user_room_id) => {
+                if !is_captcha_enabled(&user_room_id).await?
+                    || ban_listed_user(&user_room_id).await?
+                {
+                    return Ok(());
+                }
+                // Repeated knocks reuse the DM and the captcha running in it
+                let dm = match state.client.get_dm_room(&user_room_id.user_id) {
+                    Some(dm) => dm,
+                    None => state.client.create_dm(&user_room_id.user_id).await?,
+                };
+                let dm_user_room_id = UserRoomId {
+                    user_id: user_room_id.user_id.clone(),
+                    room_id: dm.room_id().into(),
+                };
+                if registry::where_is(dm_user_room_id.to_string()).is_some() {
+                    info!(user = %user_room_id, "captcha already running, ignoring knock");
+                    return Ok(());
+                }
+                info!(user = %user_room_id, "user knocked, sending captcha in DM");
+                Actor::spawn_linked(
+                    Some(dm_user_room_id.to_string()),
+                    CaptchaMonitor,
+                    CaptchaInit {
+                        user_room_id,
+                        client: state.client.clone(),
+                        challenge_room_id: dm_user_room_id.room_id,
+                        admission: CaptchaAdmission::Knock,
+                    },
+                    myself.into(),
+                )
+                .await?;
+            }
+            SpawnerMessage::Admitted(user_room_id) => {
+                state
+                    .admitted
+                    .retain(|_, admitted_at| admitted_at.elapsed() < PENDING_INVITE_TIMEOUT);
+                state
+                    .admitted
+                    .insert(user_room_id.to_string(), Instant::now());
+            }
         };
 
         Ok(())
@@ -4,7 +4,11 @@ use matrix_sdk::Client;
 use ractor::{Actor, ActorProcessingErr, ActorRef, SupervisionEvent};
 use tracing::{error, info};
 
-use super::{config_provider::ConfigProvider, moderator::Moderator, spawner::Spawner};
+use super::{
+    appeal::Appeal, auto_join::AutoJoin, commander::Commander, config_provider::ConfigProvider,
+    moderator::Moderator, policy_list::PolicyList, reputation::Reputation,
+    room_upgrade::RoomUpgrade, server_acl::ServerAcl, space::Space, spawner::Spawner,
+};
 
 pub(crate) struct Supervisor;
 
@@ -23,11 +27,12 @@ async fn start_spawner(myself: &ActorRef<SupervisorMessage>, client: Client) ->
 async fn start_config_provider(
     myself: &ActorRef<SupervisorMessage>,
     config_path: PathBuf,
+    client: Client,
 ) -> anyhow::Result<()> {
     Actor::spawn_linked(
         Some("config_provider".into()),
         ConfigProvider,
-        config_path,
+        (config_path, client),
         myself.get_cell(),
     )
     .await?;
@@ -48,6 +53,100 @@ async fn start_moderator(
     Ok(())
 }
 
+async fn start_reputation(
+    myself: &ActorRef<SupervisorMessage>,
+    client: Client,
+) -> anyhow::Result<()> {
+    Actor::spawn_linked(
+        Some("reputation".into()),
+        Reputation,
+        client,
+        myself.get_cell(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn start_commander(
+    myself: &ActorRef<SupervisorMessage>,
+    client: Client,
+) -> anyhow::Result<()> {
+    Actor::spawn_linked(
+        Some("commander".into()),
+        Commander,
+        client,
+        myself.get_cell(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn start_policy_list(
+    myself: &ActorRef<SupervisorMessage>,
+    client: Client,
+) -> anyhow::Result<()> {
+    Actor::spawn_linked(
+        Some("policy_list".into()),
+        PolicyList,
+        client,
+        myself.get_cell(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn start_server_acl(
+    myself: &ActorRef<SupervisorMessage>,
+    client: Client,
+) -> anyhow::Result<()> {
+    Actor::spawn_linked(
+        Some("server_acl".into()),
+        ServerAcl,
+        client,
+        myself.get_cell(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn start_space(myself: &ActorRef<SupervisorMessage>, client: Client) -> anyhow::Result<()> {
+    Actor::spawn_linked(Some("space".into()), Space, client, myself.get_cell()).await?;
+    Ok(())
+}
+
+async fn start_room_upgrade(
+    myself: &ActorRef<SupervisorMessage>,
+    client: Client,
+) -> anyhow::Result<()> {
+    Actor::spawn_linked(
+        Some("room_upgrade".into()),
+        RoomUpgrade,
+        client,
+        myself.get_cell(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn start_auto_join(
+    myself: &ActorRef<SupervisorMessage>,
+    client: Client,
+) -> anyhow::Result<()> {
+    Actor::spawn_linked(
+        Some("auto_join".into()),
+        AutoJoin,
+        client,
+        myself.get_cell(),
+    )
+    .await?;
+    Ok(())
+}
+
+async fn start_appeal(myself: &ActorRef<SupervisorMessage>, client: Client) -> anyhow::Result<()> {
+    Actor::spawn_linked(Some("appeal".into()), Appeal, client, myself.get_cell()).await?;
+    Ok(())
+}
+
 impl Actor for Supervisor {
     type Msg = SupervisorMessage;
     type State = SupervisorState;
@@ -59,8 +158,16 @@ impl Actor for Supervisor {
         args: Self::Arguments,
     ) -> Result<Self::State, ActorProcessingErr> {
         start_spawner(&myself, args.client.clone()).await?;
-        start_config_provider(&myself, args.config_path.clone()).await?;
+        start_config_provider(&myself, args.config_path.clone(), args.client.clone()).await?;
         start_moderator(&myself, args.client.clone()).await?;
+        start_reputation(&myself, args.client.clone()).await?;
+        start_commander(&myself, args.client.clone()).await?;
+        start_policy_list(&myself, args.client.clone()).await?;
+        start_server_acl(&myself, args.client.clone()).await?;
+        start_space(&myself, args.client.clone()).await?;
+        start_room_upgrade(&myself, args.client.clone()).await?;
+        start_auto_join(&myself, args.client.clone()).await?;
+        start_appeal(&myself, args.client.clone()).await?;
 
         Ok(args)
     }
@@ -85,9 +192,22 @@ impl Actor for Supervisor {
                     match name.as_str() {
                         "spawner" => start_spawner(&myself, state.client.clone()).await?,
                         "config_provider" => {
-                            start_config_provider(&myself, state.config_path.clone()).await?
+                            start_config_provider(
+                                &myself,
+                                state.config_path.clone(),
+                                state.client.clone(),
+                            )
+                            .await?
                         }
                         "moderator" => start_moderator(&myself, state.client.clone()).await?,
+                        "reputation" => start_reputation(&myself, state.client.clone()).await?,
+                        "commander" => start_commander(&myself, state.client.clone()).await?,
+                        "policy_list" => start_policy_list(&myself, state.client.clone()).await?,
+                        "server_acl" => start_server_acl(&myself, state.client.clone()).await?,
+                        "space" => start_space(&myself, state.client.clone()).await?,
+                        "room_upgrade" => start_room_upgrade(&myself, state.client.clone()).await?,
+                        "auto_join" => start_auto_join(&myself, state.client.clone()).await?,
+                        "appeal" => start_appeal(&myself, state.client.clone()).await?,
                         _ => {}
                     }
                 }
@@ -2,12 +2,69 @@ use std::{collections::HashMap, path::PathBuf};
 
 use serde::Deserialize;
 
+use crate::i18n::DEFAULT_LOCALE;
+
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct T1Config {
     pub(crate) t1bot: T1BotConfig,
     pub(crate) state_store: StateStoreConfig,
     pub(crate) monitors: MonitorConfig,
     pub(crate) rooms: HashMap<String, RoomConfig>,
+    pub(crate) policy_lists: Option<PolicyListConfig>,
+    pub(crate) server_acl: Option<ServerAclConfig>,
+    /// Rooms a ban issued in one room is repeated in
+    pub(crate) propagate_bans: Option<BanPropagation>,
+    pub(crate) auto_join: Option<AutoJoinConfig>,
+    /// ID of the room where the bot reports to the moderators
+    pub(crate) log_room: Option<String>,
+    /// Users who may run every command, in any protected room and in the log
+    /// room
+    #[serde(default)]
+    pub(crate) admins: Vec<String>,
+    /// Can be overridden per room
+    #[serde(default)]
+    pub(crate) mode: Mode,
+    /// Lets banned users appeal in a DM with the bot, requires the log room
+    pub(crate) appeals: Option<AppealConfig>,
+    /// Space each discovered child room belongs to, filled in by the space
+    /// actor
+    #[serde(skip)]
+    pub(crate) space_children: HashMap<String, String>,
+    /// Room each upgraded room replaces, filled in by the room upgrade actor
+    #[serde(skip)]
+    pub(crate) room_replacements: HashMap<String, String>,
+    /// Room ID of every alias used as a key of `rooms`, the keys are replaced
+    /// by the room IDs once resolved
+    #[serde(skip)]
+    pub(crate) room_aliases: HashMap<String, String>,
+    /// Rooms the bot joined on invite, moderated with the global monitor
+    /// config
+    #[serde(skip)]
+    pub(crate) managed_rooms: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum BanPropagation {
+    /// Rooms in the same room set
+    Set,
+    /// Every configured room
+    All,
+}
+
+/// What the moderator does with the sanctions of the monitors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Mode {
+    /// Kick, ban and redact
+    #[default]
+    Enforce,
+    /// Only log and report to the log room what would have been done. The
+    /// captcha and welcome messages are still sent.
+    Report,
+    /// Do not sanction or restrict anyone. The captcha and welcome messages
+    /// are still sent.
+    Off,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -17,6 +74,56 @@ pub(crate) struct T1BotConfig {
     pub(crate) display_name: String,
     pub(crate) device_id: String,
     pub(crate) device_name: String,
+    /// Locale of the messages the bot sends, can be overridden per room
+    pub(crate) locale: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct PolicyListConfig {
+    /// IDs of the policy rooms whose ban recommendations are enforced
+    #[serde(default)]
+    pub(crate) subscribe: Vec<String>,
+    /// ID of the policy room where the bans issued by the bot are published
+    pub(crate) publish: Option<String>,
+    /// Violation kinds whose bans are published, such as `impersonation`.
+    /// Bans of other kinds, like failed captchas, stay local.
+    #[serde(default)]
+    pub(crate) publish_kinds: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct AutoJoinConfig {
+    /// Users whose room invites are accepted
+    #[serde(default)]
+    pub(crate) users: Vec<String>,
+    /// Servers whose users' room invites are accepted
+    #[serde(default)]
+    pub(crate) servers: Vec<String>,
+}
+
+impl AutoJoinConfig {
+    pub(crate) fn is_allowed(&self, user_id: &str, server_name: &str) -> bool {
+        self.users.iter().any(|user| user == user_id)
+            || self.servers.iter().any(|server| server == server_name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct AppealConfig {
+    /// Vouch for pardoned users so the monitors trust them
+    #[serde(default)]
+    pub(crate) vouch: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct ServerAclConfig {
+    /// Servers are denied in every protected room once this many of their
+    /// accounts were sanctioned within `window_secs`
+    pub(crate) max_violations: usize,
+    pub(crate) window_secs: u64,
+    /// Globs of servers that are never denied
+    #[serde(default)]
+    pub(crate) allow: Vec<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -30,6 +137,11 @@ pub(crate) struct MonitorConfig {
     pub(crate) rate_limit: Option<RateLimitConfig>,
     pub(crate) link_spam: Option<LinkSpamConfig>,
     pub(crate) captcha: Option<CaptchaConfig>,
+    pub(crate) impersonation: Option<ImpersonationConfig>,
+    pub(crate) display_name: Option<DisplayNameConfig>,
+    pub(crate) invite: Option<InviteConfig>,
+    pub(crate) welcome: Option<WelcomeConfig>,
+    pub(crate) reputation: Option<ReputationConfig>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -38,10 +150,193 @@ pub(crate) enum RoomConfig {
     RoomEnabled(bool),
     RoomDetail {
         enabled: bool,
-        monitors: MonitorConfig,
+        locale: Option<String>,
+        mode: Option<Mode>,
+        /// Name of the set of rooms that share bans
+        set: Option<String>,
+        /// The room is a space, its child rooms are protected with its config
+        #[serde(default)]
+        space: bool,
+        monitors: Box<MonitorConfig>,
     },
 }
 
+impl T1Config {
+    /// Rejects settings that cannot work together.
+    pub(crate) fn validate(&self) -> anyhow::Result<()> {
+        let room_monitors = self.rooms.iter().filter_map(|(room_id, room)| match room {
+            RoomConfig::RoomDetail { monitors, .. } => Some((room_id.as_str(), monitors.as_ref())),
+            RoomConfig::RoomEnabled(_) => None,
+        });
+        for (scope, monitors) in std::iter::once(("monitors", &self.monitors)).chain(room_monitors)
+        {
+            // Muted members cannot react, vote or reply in the room
+            if let Some(captcha) = &monitors.captcha
+                && captcha.quarantine == Some(Quarantine::PowerLevel)
+                && captcha.delivery == CaptchaDelivery::Room
+            {
+                anyhow::bail!(
+                    "{scope}: the power_level captcha quarantine requires delivery = \"dm\""
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the alias keys of `rooms` by the room IDs they resolve to.
+    pub(crate) fn resolve_aliases(&mut self, room_aliases: HashMap<String, String>) {
+        for (alias, room_id) in &room_aliases {
+            if let Some(room) = self.rooms.remove(alias) {
+                self.rooms.insert(room_id.clone(), room);
+            }
+        }
+        self.room_aliases = room_aliases;
+    }
+
+    /// Returns the key of the room in the config file, its alias if the room
+    /// was configured by alias.
+    pub(crate) fn room_key<'a>(&'a self, room_id: &'a str) -> &'a str {
+        self.room_aliases
+            .iter()
+            .find(|(_, id)| *id == room_id)
+            .map_or(room_id, |(alias, _)| alias)
+    }
+
+    /// Returns the config of the room, of the space it belongs to, or of the
+    /// room it replaces. Rooms can be looked up by room ID or by alias.
+    fn room(&self, room_id: &str) -> Option<&RoomConfig> {
+        let room_id = self
+            .room_aliases
+            .get(room_id)
+            .map_or(room_id, String::as_str);
+        self.rooms
+            .get(room_id)
+            .or_else(|| {
+                self.space_children
+                    .get(room_id)
+                    .and_then(|space| self.rooms.get(space))
+            })
+            .or_else(|| {
+                self.room_replacements
+                    .get(room_id)
+                    .and_then(|old| self.room(old))
+            })
+    }
+
+    /// Returns the configured spaces whose child rooms are protected.
+    pub(crate) fn spaces(&self) -> impl Iterator<Item = &str> {
+        self.rooms.iter().filter_map(|(room_id, room)| match room {
+            RoomConfig::RoomDetail {
+                enabled: true,
+                space: true,
+                ..
+            } => Some(room_id.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns whether the bot should moderate the room. Rooms that are not
+    /// listed in the config are moderated with the global monitor config.
+    /// Policy rooms and the log room are never moderated.
+    pub(crate) fn is_room_enabled(&self, room_id: &str) -> bool {
+        if self.log_room.as_deref() == Some(room_id) {
+            return false;
+        }
+        if let Some(policy_lists) = &self.policy_lists
+            && (policy_lists.subscribe.iter().any(|id| id == room_id)
+                || policy_lists.publish.as_deref() == Some(room_id))
+        {
+            return false;
+        }
+        match self.room(room_id) {
+            Some(RoomConfig::RoomEnabled(enabled)) => *enabled,
+            Some(RoomConfig::RoomDetail { enabled, .. }) => *enabled,
+            None => true,
+        }
+    }
+
+    /// Returns whether the room is moderated and was configured, discovered
+    /// through a space or an upgrade, or joined on invite. Unlike
+    /// `is_room_enabled` this is false for unlisted rooms such as DMs.
+    pub(crate) fn is_protected_room(&self, room_id: &str) -> bool {
+        self.is_room_enabled(room_id)
+            && (self.room(room_id).is_some() || self.managed_rooms.iter().any(|id| id == room_id))
+    }
+
+    pub(crate) fn is_admin(&self, user_id: &str) -> bool {
+        self.admins.iter().any(|admin| admin == user_id)
+    }
+
+    /// Returns the other enabled rooms a ban in the room is propagated to.
+    pub(crate) fn ban_propagation_rooms(&self, room_id: &str) -> Vec<&str> {
+        let set_of = |room_id: &str| match self.room(room_id) {
+            Some(RoomConfig::RoomDetail { set, .. }) => set.as_deref(),
+            _ => None,
+        };
+        let mut rooms: Vec<&str> = self
+            .rooms
+            .keys()
+            .chain(self.space_children.keys())
+            .chain(self.room_replacements.keys())
+            .chain(&self.managed_rooms)
+            .map(String::as_str)
+            .filter(|other| *other != room_id && self.is_room_enabled(other))
+            .filter(|other| match self.propagate_bans {
+                Some(BanPropagation::All) => true,
+                Some(BanPropagation::Set) => {
+                    set_of(room_id).is_some() && set_of(room_id) == set_of(other)
+                }
+                None => false,
+            })
+            .collect();
+        // A managed room may also be configured
+        rooms.sort_unstable();
+        rooms.dedup();
+        rooms
+    }
+
+    /// Returns the path of the SQLite database recording the violations.
+    pub(crate) fn ledger_path(&self) -> PathBuf {
+        self.state_store.path.join("ledger.sqlite3")
+    }
+
+    /// Returns what the moderator does with the sanctions in the room.
+    pub(crate) fn mode(&self, room_id: &str) -> Mode {
+        match self.room(room_id) {
+            Some(RoomConfig::RoomDetail {
+                mode: Some(mode), ..
+            }) => *mode,
+            _ => self.mode,
+        }
+    }
+
+    /// Returns the locale of the messages the bot sends to the room.
+    pub(crate) fn locale(&self, room_id: &str) -> &str {
+        match self.room(room_id) {
+            Some(RoomConfig::RoomDetail {
+                locale: Some(locale),
+                ..
+            }) => locale,
+            _ => self.t1bot.locale.as_deref().unwrap_or(DEFAULT_LOCALE),
+        }
+    }
+
+    /// Returns the room specific config of a monitor, falling back to the
+    /// global monitor config.
+    pub(crate) fn monitor<T: Clone>(
+        &self,
+        room_id: &str,
+        select: impl Fn(&MonitorConfig) -> &Option<T>,
+    ) -> Option<T> {
+        self.room(room_id)
+            .and_then(|room| match room {
+                RoomConfig::RoomEnabled(_) => None,
+                RoomConfig::RoomDetail { monitors, .. } => select(monitors).clone(),
+            })
+            .or_else(|| select(&self.monitors).clone())
+    }
+}
+
 #[derive(Debug, Clone, Default, Deserialize)]
 pub(crate) struct RateLimitConfig {
     pub(crate) token_new: f32,
@@ -63,10 +358,386 @@ pub(crate) struct CaptchaConfig {
     pub(crate) timeout_secs: u64,
     #[serde(default)]
     pub(crate) questions: Vec<CaptchaQuestion>,
+    /// Question banks for rooms with a matching locale, used instead of
+    /// `questions`
+    #[serde(default)]
+    pub(crate) localized_questions: HashMap<String, Vec<CaptchaQuestion>>,
+    #[serde(default)]
+    pub(crate) delivery: CaptchaDelivery,
+    #[serde(default)]
+    pub(crate) presentation: CaptchaPresentation,
+    /// Number of questions a user may answer before the sanction is applied.
+    /// Every wrong answer is followed by a different question.
+    #[serde(default = "default_captcha_attempts")]
+    pub(crate) attempts: u32,
+    /// Applied when the user runs out of attempts or time
+    #[serde(default)]
+    pub(crate) sanction: Sanction,
+    /// Restricts new members until they pass the captcha
+    pub(crate) quarantine: Option<Quarantine>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Quarantine {
+    /// Lower the power level of the user below `events_default`
+    PowerLevel,
+    /// Redact every message the user sends
+    Redact,
+}
+
+impl CaptchaConfig {
+    pub(crate) fn questions_for(&self, locale: &str) -> &[CaptchaQuestion] {
+        self.localized_questions
+            .get(locale)
+            .unwrap_or(&self.questions)
+    }
+}
+
+fn default_captcha_attempts() -> u32 {
+    1
+}
+
+/// Action taken against a user who violated the room rules
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Sanction {
+    #[default]
+    Kick,
+    Ban,
+}
+
+impl Sanction {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Sanction::Kick => "kick",
+            Sanction::Ban => "ban",
+        }
+    }
+}
+
+/// Where the captcha question is posted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CaptchaDelivery {
+    #[default]
+    Room,
+    /// In a direct message with the new member
+    Dm,
+}
+
+/// How multiple-choice questions are answered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CaptchaPresentation {
+    /// By reacting to the question with the emoji of a choice
+    #[default]
+    Reactions,
+    /// By voting in a poll, for clients that support polls. Questions with
+    /// typed answers are still sent as messages.
+    Poll,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum CaptchaQuestion {
+    /// Answered by reacting with the keycap emoji of the answer number
+    Choice { body: String, answer: u8 },
+    /// Answered by reacting with the emoji of a correct choice. Choices are
+    /// listed in a different order for every challenge.
+    Choices {
+        body: String,
+        choices: Vec<CaptchaChoice>,
+    },
+    /// Answered by replying with any of the accepted answers, ignoring case
+    /// and whitespace
+    Text { body: String, accept: Vec<String> },
+    /// A new puzzle is generated for every challenge
+    Generated { generate: PuzzleKind },
 }
 
 #[derive(Debug, Clone, Deserialize)]
-pub(crate) struct CaptchaQuestion {
-    pub(crate) body: String,
-    pub(crate) answer: u8,
+pub(crate) struct CaptchaChoice {
+    pub(crate) label: String,
+    /// Reaction used to pick this choice, numbered keycaps by default
+    pub(crate) emoji: Option<String>,
+    #[serde(default)]
+    pub(crate) correct: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PuzzleKind {
+    /// Addition, subtraction or multiplication of small numbers
+    Arithmetic,
+    /// Spelling a word backwards or counting its letters
+    Word,
+    /// Typing the characters of a distorted image
+    Image,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct ImpersonationConfig {
+    /// Members at or above this power level are protected from impersonation
+    #[serde(default = "default_protected_power_level")]
+    pub(crate) power_level: i64,
+    /// Additional display names that nobody else may use
+    #[serde(default)]
+    pub(crate) protected_names: Vec<String>,
+}
+
+fn default_protected_power_level() -> i64 {
+    50
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct DisplayNameConfig {
+    /// Treat display names containing links as spam
+    #[serde(default)]
+    pub(crate) block_links: bool,
+    /// Display names containing any of these are not allowed. Matching is
+    /// case-insensitive and folds Unicode confusables.
+    #[serde(default)]
+    pub(crate) keywords: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct InviteConfig {
+    /// Maximum number of invites a user may send within `window_secs`
+    pub(crate) max_invites: usize,
+    pub(crate) window_secs: u64,
+    /// Invites sent by users who joined less than this many seconds ago are
+    /// revoked
+    #[serde(default)]
+    pub(crate) new_member_timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct WelcomeConfig {
+    /// Notice sent to new members after the captcha, `{name}` is replaced by
+    /// their display name
+    pub(crate) message: String,
+    /// Rules appended to the welcome notice. New members must accept them by
+    /// reacting to the notice before the quarantine is lifted.
+    pub(crate) rules: Option<String>,
+}
+
+/// Scores are earned by time since the user was first seen, messages,
+/// captchas passed and moderator vouches, and lost by violations.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct ReputationConfig {
+    /// Users at or above this score skip the captcha and the new member rate
+    /// limit
+    pub(crate) trusted_score: i64,
+    /// Users below this score stay on the new member rate limit
+    pub(crate) untrusted_score: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses the config, prefixed by the sections every config needs.
+    fn parse(config: &str) -> T1Config {
+        toml::from_str(&format!(
+            r#"
+            {config}
+
+            [t1bot]
+            user_id = "@t1bot:example.org"
+            password = "secret"
+            display_name = "T1"
+            device_id = "T1"
+            device_name = "T1"
+
+            [state_store]
+            path = "store"
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn config(propagate_bans: &str) -> T1Config {
+        let mut config = parse(&format!(
+            r#"
+            propagate_bans = "{propagate_bans}"
+            log_room = "!log:example.org"
+
+            [monitors]
+
+            [rooms]
+            "!a:example.org" = {{ enabled = true, set = "one", monitors = {{}} }}
+            "!b:example.org" = {{ enabled = true, set = "one", monitors = {{}} }}
+            "!c:example.org" = {{ enabled = true, set = "two", monitors = {{}} }}
+            "!d:example.org" = {{ enabled = false, set = "one", monitors = {{}} }}
+            "!e:example.org" = true
+            "!log:example.org" = true
+            "#
+        ));
+        config.managed_rooms = vec!["!b:example.org".into(), "!m:example.org".into()];
+        config
+            .space_children
+            .insert("!child:example.org".into(), "!a:example.org".into());
+        config
+    }
+
+    #[test]
+    fn ban_propagation_to_set() {
+        let config = config("set");
+        assert_eq!(
+            config.ban_propagation_rooms("!a:example.org"),
+            ["!b:example.org", "!child:example.org"]
+        );
+        assert_eq!(
+            config.ban_propagation_rooms("!child:example.org"),
+            ["!a:example.org", "!b:example.org"]
+        );
+        assert!(config.ban_propagation_rooms("!c:example.org").is_empty());
+        assert!(config.ban_propagation_rooms("!e:example.org").is_empty());
+    }
+
+    #[test]
+    fn ban_propagation_to_all() {
+        let config = config("all");
+        assert_eq!(
+            config.ban_propagation_rooms("!a:example.org"),
+            [
+                "!b:example.org",
+                "!c:example.org",
+                "!child:example.org",
+                "!e:example.org",
+                "!m:example.org",
+            ]
+        );
+    }
+
+    #[test]
+    fn ban_propagation_disabled() {
+        let mut config = config("all");
+        config.propagate_bans = None;
+        assert!(config.ban_propagation_rooms("!a:example.org").is_empty());
+    }
+
+    #[test]
+    fn validate_power_level_quarantine() {
+        let config = parse(
+            r#"
+            [monitors]
+            captcha = { timeout_secs = 60, delivery = "dm", quarantine = "power_level" }
+
+            [rooms]
+            "!a:example.org" = { enabled = true, monitors = { captcha = { timeout_secs = 60, quarantine = "redact" } } }
+            "#,
+        );
+        assert!(config.validate().is_ok());
+
+        let config = parse(
+            r#"
+            [monitors]
+
+            [rooms]
+            "!a:example.org" = { enabled = true, monitors = { captcha = { timeout_secs = 60, quarantine = "power_level" } } }
+            "#,
+        );
+        assert!(config.validate().is_err());
+    }
+
+    /// Rooms with distinct locales, so that `locale` tells which config
+    /// `room` found
+    fn localized_rooms() -> T1Config {
+        parse(
+            r##"
+            [monitors]
+
+            [rooms]
+            "!room:example.org" = { enabled = true, locale = "ja", monitors = {} }
+            "#alias:example.org" = { enabled = true, locale = "zh-TW", monitors = {} }
+            "!space:example.org" = { enabled = true, space = true, locale = "ja", monitors = {} }
+            "##,
+        )
+    }
+
+    #[test]
+    fn room_space_child() {
+        let mut config = localized_rooms();
+        config
+            .space_children
+            .insert("!child:example.org".into(), "!space:example.org".into());
+        assert_eq!(config.locale("!child:example.org"), "ja");
+        assert!(config.room("!child:example.org").is_some());
+        assert!(config.room("!other:example.org").is_none());
+    }
+
+    #[test]
+    fn room_replacement() {
+        let mut config = localized_rooms();
+        config
+            .room_replacements
+            .insert("!new:example.org".into(), "!room:example.org".into());
+        config
+            .room_replacements
+            .insert("!newer:example.org".into(), "!new:example.org".into());
+        assert_eq!(config.locale("!new:example.org"), "ja");
+        // A room upgraded twice still finds the original config
+        assert_eq!(config.locale("!newer:example.org"), "ja");
+        assert!(config.room("!unrelated:example.org").is_none());
+    }
+
+    #[test]
+    fn room_by_alias() {
+        let mut config = localized_rooms();
+        config.resolve_aliases(HashMap::from([(
+            "#alias:example.org".to_string(),
+            "!aliased:example.org".to_string(),
+        )]));
+        assert!(config.rooms.contains_key("!aliased:example.org"));
+        assert!(!config.rooms.contains_key("#alias:example.org"));
+        assert_eq!(config.locale("!aliased:example.org"), "zh-TW");
+        assert_eq!(config.locale("#alias:example.org"), "zh-TW");
+        assert_eq!(
+            config.room_key("!aliased:example.org"),
+            "#alias:example.org"
+        );
+    }
+
+    #[test]
+    fn room_by_id() {
+        let mut config = localized_rooms();
+        config.resolve_aliases(HashMap::new());
+        assert_eq!(config.locale("!room:example.org"), "ja");
+        assert_eq!(config.room_key("!room:example.org"), "!room:example.org");
+    }
+
+    #[test]
+    fn room_unknown() {
+        let mut config = localized_rooms();
+        config.resolve_aliases(HashMap::from([(
+            "#alias:example.org".to_string(),
+            "!aliased:example.org".to_string(),
+        )]));
+        assert!(config.room("!unknown:example.org").is_none());
+        assert!(config.room("#unknown:example.org").is_none());
+        assert_eq!(config.locale("!unknown:example.org"), DEFAULT_LOCALE);
+        assert_eq!(
+            config.room_key("!unknown:example.org"),
+            "!unknown:example.org"
+        );
+        // Unlisted rooms are moderated, but not protected
+        assert!(config.is_room_enabled("!unknown:example.org"));
+        assert!(!config.is_protected_room("!unknown:example.org"));
+        assert!(config.is_protected_room("!aliased:example.org"));
+    }
+
+    #[test]
+    fn auto_join_allowlist() {
+        let auto_join = AutoJoinConfig {
+            users: vec!["@admin:example.org".into()],
+            servers: vec!["trusted.org".into()],
+        };
+        assert!(auto_join.is_allowed("@admin:example.org", "example.org"));
+        assert!(auto_join.is_allowed("@anyone:trusted.org", "trusted.org"));
+        assert!(!auto_join.is_allowed("@other:example.org", "example.org"));
+        assert!(!auto_join.is_allowed("@admin:evil.org", "evil.org"));
+        assert!(!AutoJoinConfig::default().is_allowed("@admin:example.org", "example.org"));
+    }
 }
@@ -0,0 +1,130 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use fluent_bundle::{concurrent::FluentBundle, FluentArgs, FluentResource, FluentValue};
+use tracing::error;
+use unic_langid::LanguageIdentifier;
+
+pub(crate) const DEFAULT_LOCALE: &str = "en";
+
+const CATALOGS: [(&str, &str); 3] = [
+    ("en", include_str!("../locales/en/t1bot.ftl")),
+    ("ja", include_str!("../locales/ja/t1bot.ftl")),
+    ("zh-TW", include_str!("../locales/zh-TW/t1bot.ftl")),
+];
+
+static BUNDLES: LazyLock<HashMap<&str, FluentBundle<FluentResource>>> = LazyLock::new(|| {
+    CATALOGS
+        .iter()
+        .map(|(locale, source)| {
+            let langid: LanguageIdentifier = locale.parse().expect("valid locale identifier");
+            let resource =
+                FluentResource::try_new(source.to_string()).expect("valid message catalog");
+            let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+            // Isolation marks end up as invisible characters in Matrix messages
+            bundle.set_use_isolating(false);
+            bundle
+                .add_resource(resource)
+                .expect("unique message identifiers");
+            (*locale, bundle)
+        })
+        .collect()
+});
+
+/// Formats a message from the catalog of the locale, falling back to the
+/// default locale for unknown locales and missing messages.
+pub(crate) fn tr<'a>(
+    locale: &str,
+    id: &str,
+    args: impl IntoIterator<Item = (&'a str, FluentValue<'a>)>,
+) -> String {
+    let args = FluentArgs::from_iter(args);
+    for bundle in [BUNDLES.get(locale), BUNDLES.get(DEFAULT_LOCALE)]
+        .into_iter()
+        .flatten()
+    {
+        if let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) {
+            let mut errors = vec![];
+            let text = bundle.format_pattern(pattern, Some(&args), &mut errors);
+            if !errors.is_empty() {
+                error!(id, locale, ?errors, "Unable to format message");
+            }
+            return text.into_owned();
+        }
+    }
+    error!(id, locale, "Missing message in catalog");
+    id.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use super::*;
+
+    /// Identifiers of the messages of the catalog, with the variables each
+    /// message uses.
+    fn messages(source: &str) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut messages = BTreeMap::new();
+        let mut current = None;
+        for line in source.lines() {
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace)
+                && let Some((id, _)) = line.split_once(" =")
+            {
+                current = Some(id.trim());
+                messages.insert(id.trim(), BTreeSet::new());
+            }
+            let Some(id) = current else {
+                continue;
+            };
+            let variables = messages.entry(id).or_default();
+            for (i, _) in line.match_indices('$') {
+                let rest = &line[i + 1..];
+                let end = rest
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                    .unwrap_or(rest.len());
+                variables.insert(&rest[..end]);
+            }
+        }
+        messages
+    }
+
+    #[test]
+    fn catalogs_build() {
+        for (locale, _) in CATALOGS {
+            assert!(BUNDLES.contains_key(locale), "missing bundle for {locale}");
+        }
+    }
+
+    #[test]
+    fn catalogs_match_default() {
+        let default = CATALOGS
+            .iter()
+            .find(|(locale, _)| *locale == DEFAULT_LOCALE)
+            .map(|(_, source)| messages(source))
+            .unwrap();
+        assert!(default["log-report-ban"].contains("reason"));
+        for (locale, source) in CATALOGS {
+            let messages = messages(source);
+            assert_eq!(
+                messages.keys().collect::<Vec<_>>(),
+                default.keys().collect::<Vec<_>>(),
+                "message identifiers of {locale}"
+            );
+            for (id, variables) in &messages {
+                assert_eq!(variables, &default[id], "variables of {id} in {locale}");
+            }
+        }
+    }
+
+    #[test]
+    fn tr_falls_back_to_default_locale() {
+        assert_eq!(
+            tr("xx", "command-admin-only", []),
+            tr(DEFAULT_LOCALE, "command-admin-only", [])
+        );
+        assert_eq!(tr(DEFAULT_LOCALE, "no-such-message", []), "no-such-message");
+    }
+}
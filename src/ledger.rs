@@ -0,0 +1,240 @@
+use std::{
+    fmt::Display,
+    path::Path,
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{params, Connection};
+
+/// Persistent record of every violation the moderator handled
+pub(crate) struct Ledger {
+    /// Locked so the moderator state can be shared across await points
+    connection: Mutex<Connection>,
+}
+
+/// What happened to the sanction of a violation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Outcome {
+    Enforced,
+    /// Reported to the log room in report mode
+    Reported,
+    /// Moderation is off in the room
+    Ignored,
+    /// The bot lacks the power for the sanction
+    MissingPermission,
+    Failed,
+}
+
+impl Outcome {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Enforced => "enforced",
+            Outcome::Reported => "reported",
+            Outcome::Ignored => "ignored",
+            Outcome::MissingPermission => "missing permission",
+            Outcome::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct ViolationRecord {
+    pub(crate) timestamp_secs: i64,
+    pub(crate) user_id: String,
+    pub(crate) room_id: String,
+    /// Name of the monitor that found the violation
+    pub(crate) monitor: String,
+    pub(crate) kind: String,
+    /// IDs of the events that caused the violation
+    pub(crate) evidence: Vec<String>,
+    pub(crate) sanction: String,
+    pub(crate) outcome: String,
+}
+
+impl Display for ViolationRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} in {}: {} ({}), {} {}",
+            format_timestamp(self.timestamp_secs),
+            self.user_id,
+            self.room_id,
+            self.kind,
+            self.monitor,
+            self.sanction,
+            self.outcome
+        )?;
+        if !self.evidence.is_empty() {
+            write!(f, " [{}]", self.evidence.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Selects violations, every unset field matches all
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HistoryFilter {
+    pub(crate) user_id: Option<String>,
+    pub(crate) room_id: Option<String>,
+    pub(crate) since_secs: Option<i64>,
+    pub(crate) until_secs: Option<i64>,
+    /// Returns at most this many of the most recent violations
+    pub(crate) limit: Option<u32>,
+}
+
+pub(crate) fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+/// Parses a duration such as `30m`, `12h` or `7d` into seconds.
+pub(crate) fn parse_duration(text: &str) -> Option<i64> {
+    let unit = match text.chars().last()? {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+    let count: u32 = text[..text.len() - 1].parse().ok()?;
+    Some(i64::from(count) * unit)
+}
+
+/// Formats the unix time as an UTC date and time.
+fn format_timestamp(secs: i64) -> String {
+    let days = secs.div_euclid(24 * 60 * 60);
+    let time = secs.rem_euclid(24 * 60 * 60);
+    // Civil date from days since the epoch, by Howard Hinnant
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+impl Ledger {
+    pub(crate) fn open(path: &Path) -> rusqlite::Result<Ledger> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS violations (
+                id INTEGER PRIMARY KEY,
+                timestamp INTEGER NOT NULL,
+                user_id TEXT NOT NULL,
+                room_id TEXT NOT NULL,
+                monitor TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                evidence TEXT NOT NULL,
+                sanction TEXT NOT NULL,
+                outcome TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS violations_user ON violations (user_id, timestamp);
+            CREATE INDEX IF NOT EXISTS violations_room ON violations (room_id, timestamp);",
+        )?;
+        Ok(Ledger {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn insert(&self, record: &ViolationRecord) -> rusqlite::Result<()> {
+        self.connection().execute(
+            "INSERT INTO violations
+                (timestamp, user_id, room_id, monitor, kind, evidence, sanction, outcome)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                record.timestamp_secs,
+                record.user_id,
+                record.room_id,
+                record.monitor,
+                record.kind,
+                record.evidence.join(" "),
+                record.sanction,
+                record.outcome,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the matching violations, oldest first.
+    pub(crate) fn query(&self, filter: &HistoryFilter) -> rusqlite::Result<Vec<ViolationRecord>> {
+        let connection = self.connection();
+        let mut statement = connection.prepare(
+            "SELECT timestamp, user_id, room_id, monitor, kind, evidence, sanction, outcome
+            FROM violations
+            WHERE (?1 IS NULL OR user_id = ?1)
+                AND (?2 IS NULL OR room_id = ?2)
+                AND (?3 IS NULL OR timestamp >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?5",
+        )?;
+        let rows = statement.query_map(
+            params![
+                filter.user_id,
+                filter.room_id,
+                filter.since_secs,
+                filter.until_secs,
+                filter.limit.map_or(-1, i64::from),
+            ],
+            |row| {
+                let evidence: String = row.get(5)?;
+                Ok(ViolationRecord {
+                    timestamp_secs: row.get(0)?,
+                    user_id: row.get(1)?,
+                    room_id: row.get(2)?,
+                    monitor: row.get(3)?,
+                    kind: row.get(4)?,
+                    evidence: evidence.split_whitespace().map(String::from).collect(),
+                    sanction: row.get(6)?,
+                    outcome: row.get(7)?,
+                })
+            },
+        )?;
+        let mut records = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        records.reverse();
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("45s"), Some(45));
+        assert_eq!(parse_duration("30m"), Some(30 * 60));
+        assert_eq!(parse_duration("12h"), Some(12 * 60 * 60));
+        assert_eq!(parse_duration("7d"), Some(7 * 24 * 60 * 60));
+        assert_eq!(parse_duration("0d"), Some(0));
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("d"), None);
+        assert_eq!(parse_duration("7"), None);
+        assert_eq!(parse_duration("7w"), None);
+        assert_eq!(parse_duration("-7d"), None);
+        assert_eq!(parse_duration("1.5h"), None);
+        assert_eq!(parse_duration("7日"), None);
+    }
+}
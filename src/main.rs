@@ -1,21 +1,39 @@
-use std::{fs, path::PathBuf};
+use std::fs;
 
 use actors::{
+    appeal::AppealMessage,
+    auto_join::AutoJoinMessage,
+    commander::{CommanderMessage, COMMAND_PREFIX},
+    moderator::ModeratorMessage,
     monitor::MonitorMessage,
+    policy_list::PolicyListMessage,
+    room_upgrade::RoomUpgradeMessage,
+    space::SpaceMessage,
     spawner::SpawnerMessage,
     supervisor::{Supervisor, SupervisorState},
 };
 use config::T1Config;
+use ledger::{now_secs, parse_duration, HistoryFilter, Ledger};
 use matrix::UserRoomId;
 use matrix_sdk::{
     config::{RequestConfig, SyncSettings},
     ruma::{
         events::{
+            policy::rule::{
+                room::SyncPolicyRuleRoomEvent, server::SyncPolicyRuleServerEvent,
+                user::SyncPolicyRuleUserEvent,
+            },
+            poll::unstable_response::OriginalSyncUnstablePollResponseEvent,
             reaction::SyncReactionEvent,
             room::{
-                member::{MembershipState, SyncRoomMemberEvent},
+                member::{
+                    MembershipChange, MembershipState, StrippedRoomMemberEvent, SyncRoomMemberEvent,
+                },
                 message::SyncRoomMessageEvent,
+                power_levels::SyncRoomPowerLevelsEvent,
+                tombstone::OriginalSyncRoomTombstoneEvent,
             },
+            space::child::SyncSpaceChildEvent,
         },
         MilliSecondsSinceUnixEpoch, RoomOrAliasId, UserId,
     },
@@ -26,22 +44,89 @@ use tokio::{signal::unix::SignalKind, time::Duration};
 
 mod actors;
 mod config;
+mod i18n;
+mod ledger;
 mod matrix;
 
+mod flags {
+    use std::path::PathBuf;
+
+    xflags::xflags! {
+        cmd t1bot {
+            /// Path to the config file (TOML)
+            required -c, --config config_path: PathBuf
+            /// Run the bot
+            default cmd run {}
+            /// Print the recorded violations
+            cmd history {
+                /// Only violations of this user
+                optional --user user_id: String
+                /// Only violations in this room
+                optional --room room_id: String
+                /// Only violations since this long ago, e.g. 30m, 12h or 7d
+                optional --since since: String
+                /// Only violations until this long ago
+                optional --until until: String
+            }
+        }
+    }
+}
+
 const MAX_MESSAGE_DELAY_MS: u32 = 10_000;
 
+fn check_permissions(room: &Room) -> anyhow::Result<()> {
+    if let Some(moderator) = ActorRef::<ModeratorMessage>::where_is("moderator".into()) {
+        moderator.cast(ModeratorMessage::CheckPermissions(room.room_id().into()))?;
+    }
+    Ok(())
+}
+
+fn reload_policy_list(room: &Room) -> anyhow::Result<()> {
+    if let Some(policy_list) = ActorRef::<PolicyListMessage>::where_is("policy_list".into()) {
+        policy_list.cast(PolicyListMessage::Reload(room.room_id().into()))?;
+    }
+    Ok(())
+}
+
+/// Prints the violations recorded in the ledger.
+fn print_history(config: &T1Config, history: flags::History) -> anyhow::Result<()> {
+    let ago = |duration: Option<String>| -> anyhow::Result<Option<i64>> {
+        duration
+            .map(|duration| {
+                parse_duration(&duration)
+                    .map(|secs| now_secs() - secs)
+                    .ok_or_else(|| anyhow::anyhow!("invalid duration {duration}"))
+            })
+            .transpose()
+    };
+    let filter = HistoryFilter {
+        user_id: history.user,
+        room_id: history.room,
+        since_secs: ago(history.since)?,
+        until_secs: ago(history.until)?,
+        limit: None,
+    };
+    let ledger = Ledger::open(&config.ledger_path())?;
+    for record in ledger.query(&filter)? {
+        println!("{record}");
+    }
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
 
-    let flags = xflags::parse_or_exit! {
-        /// Path to the config file (TOML)
-        required -c, --config config_path: PathBuf
-    };
+    let flags = flags::T1bot::from_env_or_exit();
 
     let config_text = fs::read_to_string(&flags.config)?;
     let config: T1Config = toml::from_str(&config_text)?;
 
+    if let flags::T1botCmd::History(history) = flags.subcommand {
+        return print_history(&config, history);
+    }
+    config.validate()?;
+
     let t1bot = UserId::parse(&config.t1bot.user_id)?;
     let client = Client::builder()
         .server_name(t1bot.server_name())
@@ -105,8 +190,34 @@ async fn main() -> anyhow::Result<()> {
                 user_id: ev.sender().into(),
                 room_id: room.room_id().into(),
             };
+            // DMs carry captcha answers and appeals, never commands
+            if room.is_direct().await? {
+                if let Some(monitor) =
+                    ActorRef::<MonitorMessage>::where_is(user_room_id.to_string())
+                {
+                    monitor.cast(MonitorMessage::RoomMessage(Box::new(ev)))?;
+                } else if let Some(msg) = ev.as_original()
+                    && let Some(appeal) = ActorRef::<AppealMessage>::where_is("appeal".into())
+                {
+                    appeal.cast(AppealMessage::DirectMessage {
+                        user_room_id,
+                        body: msg.content.body().to_string(),
+                    })?;
+                }
+                return Ok(());
+            }
+            // Commands are still moderated like any other message
+            if let Some(msg) = ev.as_original()
+                && msg.content.body().starts_with(COMMAND_PREFIX)
+                && let Some(commander) = ActorRef::<CommanderMessage>::where_is("commander".into())
+            {
+                commander.cast(CommanderMessage::Command {
+                    user_room_id: user_room_id.clone(),
+                    body: msg.content.body().to_string(),
+                })?;
+            }
             if let Some(monitor) = ActorRef::<MonitorMessage>::where_is(user_room_id.to_string()) {
-                monitor.cast(MonitorMessage::RoomMessage(ev))?;
+                monitor.cast(MonitorMessage::RoomMessage(Box::new(ev)))?;
             } else if let Some(spawner) = ActorRef::<SpawnerMessage>::where_is("spawner".into()) {
                 spawner.cast(SpawnerMessage::RegisterUser(user_room_id))?;
             }
@@ -119,6 +230,9 @@ async fn main() -> anyhow::Result<()> {
         async move |ev: SyncRoomMemberEvent, room: Room| -> anyhow::Result<()> {
             if let Some(ev) = ev.as_original() {
                 if ev.state_key == my_id {
+                    if ev.content.membership == MembershipState::Join {
+                        check_permissions(&room)?;
+                    }
                     return Ok(());
                 }
                 if ev.state_key.server_name().host() == "t2bot.io" {
@@ -131,12 +245,47 @@ async fn main() -> anyhow::Result<()> {
                 };
                 match ev.content.membership {
                     MembershipState::Join => {
-                        if let Some(spawner) =
+                        if matches!(
+                            ev.membership_change(),
+                            MembershipChange::ProfileChanged { .. }
+                        ) {
+                            if let Some(monitor) =
+                                ActorRef::<MonitorMessage>::where_is(user_room_id.to_string())
+                            {
+                                monitor.cast(MonitorMessage::MemberChange(Box::new(ev.clone())))?;
+                            } else if let Some(spawner) =
+                                ActorRef::<SpawnerMessage>::where_is("spawner".into())
+                            {
+                                spawner.cast(SpawnerMessage::RegisterUser(user_room_id))?;
+                            }
+                        } else if let Some(spawner) =
                             ActorRef::<SpawnerMessage>::where_is("spawner".into())
                         {
                             spawner.cast(SpawnerMessage::RegisterUserJoin(user_room_id))?;
                         }
                     }
+                    // Invites sent by the bot, for captcha DMs and admitted
+                    // knocks, are not limited
+                    MembershipState::Invite if ev.sender != my_id => {
+                        if let Some(spawner) =
+                            ActorRef::<SpawnerMessage>::where_is("spawner".into())
+                        {
+                            spawner.cast(SpawnerMessage::Invite {
+                                inviter: UserRoomId {
+                                    user_id: ev.sender.clone(),
+                                    room_id: room.room_id().into(),
+                                },
+                                invitee: user_room_id.user_id,
+                            })?;
+                        }
+                    }
+                    MembershipState::Knock => {
+                        if let Some(spawner) =
+                            ActorRef::<SpawnerMessage>::where_is("spawner".into())
+                        {
+                            spawner.cast(SpawnerMessage::Knock(user_room_id))?;
+                        }
+                    }
                     MembershipState::Leave => {
                         if let Some(monitor) =
                             ActorRef::<MonitorMessage>::where_is(user_room_id.to_string())
@@ -174,14 +323,112 @@ async fn main() -> anyhow::Result<()> {
                 room_id: room.room_id().into(),
             };
             if let Some(monitor) = ActorRef::<MonitorMessage>::where_is(user_room_id.to_string()) {
-                monitor.cast(MonitorMessage::ReactionMessage(ev))?;
+                monitor.cast(MonitorMessage::ReactionMessage(Box::new(ev)))?;
+            } else if let Some(appeal) = ActorRef::<AppealMessage>::where_is("appeal".into()) {
+                appeal.cast(AppealMessage::Reaction {
+                    room_id: user_room_id.room_id,
+                    ev: Box::new(ev),
+                })?;
+            }
+            Ok(())
+        },
+    );
+
+    let my_id = t1bot.clone();
+    client.add_event_handler(
+        async move |ev: OriginalSyncUnstablePollResponseEvent, room: Room| -> anyhow::Result<()> {
+            if MilliSecondsSinceUnixEpoch::now()
+                .get()
+                .saturating_sub(ev.origin_server_ts.get())
+                > MAX_MESSAGE_DELAY_MS.into()
+            {
+                tracing::info!(
+                    origin_server_ts = i64::from(ev.origin_server_ts.0),
+                    now = i64::from(MilliSecondsSinceUnixEpoch::now().0),
+                    "Network latency increased - ignoring messages that are too old"
+                );
+                return Ok(());
+            }
+            if ev.sender == my_id {
+                return Ok(());
+            }
+            let user_room_id = UserRoomId {
+                user_id: ev.sender.clone(),
+                room_id: room.room_id().into(),
+            };
+            if let Some(monitor) = ActorRef::<MonitorMessage>::where_is(user_room_id.to_string()) {
+                monitor.cast(MonitorMessage::PollResponse(Box::new(ev)))?;
+            }
+            Ok(())
+        },
+    );
+
+    client.add_event_handler(
+        async move |_: SyncRoomPowerLevelsEvent, room: Room| -> anyhow::Result<()> {
+            check_permissions(&room)
+        },
+    );
+
+    // Policy rules change the ban list of the room they are sent to
+    client.add_event_handler(
+        async move |_: SyncPolicyRuleUserEvent, room: Room| -> anyhow::Result<()> {
+            reload_policy_list(&room)
+        },
+    );
+    client.add_event_handler(
+        async move |_: SyncPolicyRuleServerEvent, room: Room| -> anyhow::Result<()> {
+            reload_policy_list(&room)
+        },
+    );
+    client.add_event_handler(
+        async move |_: SyncPolicyRuleRoomEvent, room: Room| -> anyhow::Result<()> {
+            reload_policy_list(&room)
+        },
+    );
+
+    // Children added to or removed from a space change the protected rooms
+    client.add_event_handler(
+        async move |_: SyncSpaceChildEvent, room: Room| -> anyhow::Result<()> {
+            if let Some(space) = ActorRef::<SpaceMessage>::where_is("space".into()) {
+                space.cast(SpaceMessage::Refresh(room.room_id().into()))?;
+            }
+            Ok(())
+        },
+    );
+
+    let my_id = t1bot.clone();
+    client.add_event_handler(
+        async move |ev: StrippedRoomMemberEvent, room: Room| -> anyhow::Result<()> {
+            if ev.state_key != my_id || ev.content.membership != MembershipState::Invite {
+                return Ok(());
+            }
+            if let Some(auto_join) = ActorRef::<AutoJoinMessage>::where_is("auto_join".into()) {
+                auto_join.cast(AutoJoinMessage::Invited {
+                    room_id: room.room_id().into(),
+                    inviter: ev.sender,
+                    is_direct: ev.content.is_direct == Some(true),
+                })?;
+            }
+            Ok(())
+        },
+    );
+
+    client.add_event_handler(
+        async move |ev: OriginalSyncRoomTombstoneEvent, room: Room| -> anyhow::Result<()> {
+            if let Some(room_upgrade) =
+                ActorRef::<RoomUpgradeMessage>::where_is("room_upgrade".into())
+            {
+                room_upgrade.cast(RoomUpgradeMessage::Tombstone {
+                    room_id: room.room_id().into(),
+                    replacement_room: ev.content.replacement_room,
+                })?;
             }
             Ok(())
         },
     );
 
     let server_names = &[t1bot.server_name().into()];
-    for room_id in config.rooms.keys() {
+    for room_id in config.rooms.keys().chain(&config.log_room) {
         client
             .join_room_by_id_or_alias(&RoomOrAliasId::parse(room_id)?, server_names)
             .await?;